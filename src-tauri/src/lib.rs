@@ -1,6 +1,7 @@
 mod tantivy_interface;
 
 use specta_typescript::Typescript;
+use tauri::Manager;
 use tauri_specta::{collect_commands, Builder};
 
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
@@ -16,15 +17,49 @@ pub fn run() {
         greet,
         tantivy_interface::init,
         tantivy_interface::index,
-        tantivy_interface::search
+        tantivy_interface::move_thread,
+        tantivy_interface::index_stats,
+        tantivy_interface::compact,
+        tantivy_interface::search,
+        tantivy_interface::cancel_search,
+        tantivy_interface::explain,
+        tantivy_interface::analyze,
+        tantivy_interface::related,
+        tantivy_interface::suggest,
+        tantivy_interface::autocomplete,
+        tantivy_interface::get_synonyms,
+        tantivy_interface::set_synonyms,
+        tantivy_interface::get_stop_word_languages,
+        tantivy_interface::set_stop_word_languages,
+        tantivy_interface::get_field_boosts,
+        tantivy_interface::set_field_boosts
     ]);
 
+    // Events are emitted with `Emitter::emit`, so their names and payloads
+    // are exported along with the commands, as are the messages of the
+    // errors which the frontend handles.
+    #[cfg(debug_assertions)]
+    let builder = builder
+        .typ::<tantivy_interface::IndexRecreated>()
+        .constant("RECREATED_EVENT", tantivy_interface::RECREATED_EVENT)
+        .constant("COMPACTED_EVENT", tantivy_interface::COMPACTED_EVENT)
+        .constant("INDEX_LOCKED", tantivy_interface::INDEX_LOCKED)
+        .constant("SEARCH_CANCELLED", tantivy_interface::CANCELLED);
+
     #[cfg(debug_assertions)]
     builder
         .export(Typescript::default(), "../src/generated/tauri-commands.ts")
         .expect("Failed to export typescript bindings");
 
     tauri::Builder::default()
+        // A second instance would fail to write to the index of the first one,
+        // so it focuses the running instance instead.
+        .plugin(tauri_plugin_single_instance::init(|app, _, _| {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.set_focus();
+            }
+        }))
+        .plugin(tauri_plugin_store::Builder::new().build())
         .invoke_handler(builder.invoke_handler())
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
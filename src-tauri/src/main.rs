@@ -3,8 +3,4 @@
 
 fn main() {
     potrin_lib::run();
-    tauri::Builder::default()
-        .plugin(tauri_plugin_store::Builder::new().build())
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
 }
@@ -1,21 +1,53 @@
+mod autocomplete;
 mod cjk_bigram_tokenizer;
+mod compaction;
+mod facets;
+mod field_boosts;
+mod grouping;
+mod locking;
+mod pattern;
+mod query;
+mod ranking;
+mod recovery;
+mod related;
+mod search_session;
+mod settings;
+mod spelling;
+mod stats;
+mod stop_words;
+mod synonyms;
+mod threads;
+mod uuid_v7;
+
+pub use compaction::COMPACTED_EVENT;
+pub use locking::INDEX_LOCKED;
+pub use recovery::{IndexRecreated, RECREATED_EVENT};
+pub use search_session::CANCELLED;
 
 use anyhow::anyhow;
 use cjk_bigram_tokenizer::CJKBigramTokenizer;
+use compaction::CompactionReport;
 use diacritics::remove_diacritics;
+use facets::Facets;
+use field_boosts::FieldBoosts;
+use grouping::ThreadGroupCollector;
+use locking::{IndexLockedError, WriterGuard};
+use query::{Fuzziness, QueryBuilder, QueryField, QueryNode, SearchFields, SearchMode};
+use ranking::{RecencyBoost, SortBy, CREATED_AT, OUTLINE, UPDATED_AT};
+use search_session::SearchSession;
 use serde::{Deserialize, Serialize};
 use specta::Type;
-use std::fs;
-use std::path::Path;
+use stats::IndexStats;
+use std::path::{Path, PathBuf};
 use std::sync::{Mutex, OnceLock};
-use tantivy::collector::TopDocs;
-use tantivy::directory::{ManagedDirectory, MmapDirectory};
-use tantivy::query::QueryParser;
+use stop_words::StopWordLanguage;
+use tantivy::collector::{Collector, Count, FacetCollector, TopDocs};
+use tantivy::query::{AllQuery, Query, TermQuery};
 use tantivy::tokenizer::{Language, LowerCaser, Stemmer};
-use tantivy::tokenizer::{TextAnalyzer, TokenizerManager};
-use tantivy::{doc, schema::*, IndexReader};
-use tantivy::{Index, IndexWriter};
-use tauri::{AppHandle, Manager};
+use tantivy::tokenizer::{TextAnalyzer, TokenStream};
+use tantivy::{doc, schema::*, DateTime, DocAddress, IndexReader, Searcher};
+use tantivy::{Index, IndexWriter, TantivyError};
+use tauri::{AppHandle, Emitter, Manager};
 use unicode_normalization::UnicodeNormalization;
 
 #[cfg_attr(debug_assertions, derive(Type, Debug))]
@@ -23,7 +55,37 @@ use unicode_normalization::UnicodeNormalization;
 pub struct IndexTarget {
     id: String,
     doc_type: String,
+    title: Option<String>,
+    // body of the document
     text: String,
+    tags: Option<Vec<String>>,
+    // Unix times in milliseconds, as returned by `Date.now()`.
+    // `created_at` defaults to the timestamp of the id if it is a UUIDv7,
+    // and `updated_at` defaults to `created_at`.
+    created_at: Option<f64>,
+    updated_at: Option<f64>,
+    // Ids of the threads from the root down to the thread containing the
+    // document. The path of a thread ends with its own id.
+    // Use `move_thread` when a thread is moved so that its subtree follows.
+    thread_path: Option<Vec<String>>,
+    // Fractional indexes along the thread path, followed by the fractional
+    // index of the card itself for cards. Used to sort in outline order.
+    outline_path: Option<Vec<String>>,
+}
+
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct SearchOptions {
+    mode: SearchMode,
+    fuzziness: Fuzziness,
+    limit: u8,
+    sort_by: SortBy,
+    recency_boost: Option<RecencyBoost>,
+    // Counts every matching document, not only the returned ones.
+    facets: bool,
+    // Groups the hits by thread, keeping this many hits per thread.
+    // `limit` then applies to the number of threads.
+    hits_per_thread: Option<u8>,
 }
 
 #[cfg_attr(debug_assertions, derive(Type, Debug, PartialEq))]
@@ -33,15 +95,89 @@ pub struct SearchResult {
     doc_type: String,
 }
 
+/// `Index` is the analyzer of the indexed documents, and `Query` the one of
+/// the search words, which omits the single last character of a CJK run.
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum AnalyzerMode {
+    Index,
+    Query,
+}
+
+// Offsets are byte offsets in the normalized text.
+#[cfg_attr(debug_assertions, derive(Type, Debug, PartialEq))]
+#[derive(Serialize, Deserialize)]
+pub struct AnalyzedToken {
+    text: String,
+    offset_from: u32,
+    offset_to: u32,
+    position: u32,
+}
+
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize)]
+pub struct Analysis {
+    normalized: String,
+    tokens: Vec<AnalyzedToken>,
+}
+
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize)]
+pub struct SearchExplanation {
+    tree: QueryNode,
+    tokens: Vec<String>,
+    score: Option<f32>,
+    explanation: Option<String>,
+}
+
+#[cfg_attr(debug_assertions, derive(Type, Debug, PartialEq))]
+#[derive(Serialize, Deserialize)]
+pub struct Completion {
+    text: String,
+    // set when the text is the title of a thread
+    thread_id: Option<String>,
+}
+
+#[cfg_attr(debug_assertions, derive(Type, Debug, PartialEq))]
+#[derive(Serialize, Deserialize)]
+pub struct ThreadGroup {
+    // `None` for the documents outside any thread
+    thread_id: Option<String>,
+    hit_count: u32,
+    hits: Vec<SearchResult>,
+}
+
+/// Threads are ranked by the sum of the scores of their hits in grouped
+/// searches, which fill `groups` instead of `results`.
+#[cfg_attr(debug_assertions, derive(Type, Debug, PartialEq))]
+#[derive(Serialize, Deserialize)]
+pub struct SearchResponse {
+    results: Vec<SearchResult>,
+    groups: Option<Vec<ThreadGroup>>,
+    // Present when `SearchOptions::facets` is set.
+    facets: Option<Facets>,
+}
+
 static INITIALIZED: OnceLock<()> = OnceLock::new();
 
 static INDEX: OnceLock<Index> = OnceLock::new();
+// `None` for the index in memory of the tests.
+static INDEX_PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
 static READER: OnceLock<IndexReader> = OnceLock::new();
-static WRITER: OnceLock<Mutex<IndexWriter>> = OnceLock::new();
+// `None` while another process holds the writer lock of the index.
+static WRITER: OnceLock<Mutex<Option<IndexWriter>>> = OnceLock::new();
 static ID_FIELD: OnceLock<Field> = OnceLock::new();
 static TYPE_FIELD: OnceLock<Field> = OnceLock::new();
+static TITLE_FIELD: OnceLock<Field> = OnceLock::new();
 static TEXT_FIELD: OnceLock<Field> = OnceLock::new();
-static QUERY_PARSER: OnceLock<Mutex<QueryParser>> = OnceLock::new();
+static TAGS_FIELD: OnceLock<Field> = OnceLock::new();
+static CREATED_AT_FIELD: OnceLock<Field> = OnceLock::new();
+static UPDATED_AT_FIELD: OnceLock<Field> = OnceLock::new();
+static THREAD_PATH_FIELD: OnceLock<Field> = OnceLock::new();
+static CATEGORIES_FIELD: OnceLock<Field> = OnceLock::new();
+static OUTLINE_FIELD: OnceLock<Field> = OnceLock::new();
+static WORDS_FIELD: OnceLock<Field> = OnceLock::new();
+static QUERY_TOKENIZER: OnceLock<TextAnalyzer> = OnceLock::new();
 
 fn set_once_lock<T>(lock: &OnceLock<T>, value: T) -> anyhow::Result<()> {
     lock.set(value)
@@ -56,12 +192,137 @@ fn get_once_lock<T>(lock: &OnceLock<T>) -> anyhow::Result<&T> {
     Ok(result)
 }
 
+fn lock_writer() -> anyhow::Result<WriterGuard> {
+    locking::lock(get_once_lock(&WRITER)?, get_once_lock(&INDEX)?)
+}
+
+// Writes made while another process holds the writer lock of the index.
+// They are applied in order by the next write which opens the writer,
+// or by the idle job.
+enum PendingWrite {
+    Index(Vec<IndexTarget>),
+    MoveThread {
+        thread_id: String,
+        parent_path: Vec<String>,
+        outline_path: Vec<String>,
+    },
+}
+
+static PENDING_WRITES: Mutex<Vec<PendingWrite>> = Mutex::new(vec![]);
+
+fn write(pending_write: PendingWrite) -> anyhow::Result<()> {
+    PENDING_WRITES
+        .lock()
+        .map_err(|e| anyhow!(e.to_string()))?
+        .push(pending_write);
+    apply_pending_writes()
+}
+
+// Each write is committed on its own, as it was made. A write which fails
+// is dropped, and its error is returned once the others are applied.
+fn apply_pending_writes() -> anyhow::Result<()> {
+    let mut pending_writes = PENDING_WRITES.lock().map_err(|e| anyhow!(e.to_string()))?;
+    if pending_writes.is_empty() {
+        return Ok(());
+    }
+    let mut writer = match lock_writer() {
+        Err(e) if e.is::<IndexLockedError>() => return Ok(()),
+        writer => writer?,
+    };
+
+    let mut result = Ok(());
+    for pending_write in pending_writes.drain(..) {
+        let applied = match pending_write {
+            PendingWrite::Index(input) => add_documents(&mut writer, input),
+            PendingWrite::MoveThread {
+                thread_id,
+                parent_path,
+                outline_path,
+            } => move_subtree(&mut writer, thread_id, parent_path, outline_path),
+        };
+        result = result.and(applied.and_then(|()| stats::commit(&mut writer)));
+    }
+    result
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn init(app_handle: AppHandle) -> Result<(), String> {
     build_schema(Some(app_handle))
 }
 
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn get_synonyms() -> anyhow::Result<Vec<Vec<String>>> {
+    synonyms::get()
+}
+
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn set_synonyms(app_handle: AppHandle, groups: Vec<Vec<String>>) -> anyhow::Result<()> {
+    synonyms::save(&app_handle, groups)
+}
+
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn get_stop_word_languages() -> anyhow::Result<Vec<StopWordLanguage>> {
+    stop_words::get()
+}
+
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn set_stop_word_languages(
+    app_handle: AppHandle,
+    languages: Vec<StopWordLanguage>,
+) -> anyhow::Result<()> {
+    stop_words::save(&app_handle, languages)
+}
+
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn get_field_boosts() -> anyhow::Result<FieldBoosts> {
+    field_boosts::get()
+}
+
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn set_field_boosts(app_handle: AppHandle, boosts: FieldBoosts) -> anyhow::Result<()> {
+    field_boosts::save(&app_handle, boosts)
+}
+
+/// Cancels the in-flight search of the session, if any.
+/// The cancelled search returns the `SEARCH_CANCELLED` error.
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn cancel_search(session_id: String) -> anyhow::Result<()> {
+    search_session::cancel(&session_id)
+}
+
+fn normalize(text: &str) -> String {
+    remove_diacritics(text.nfc().collect::<String>().as_str())
+}
+
+fn to_date(millis: f64) -> DateTime {
+    DateTime::from_timestamp_millis(millis as i64)
+}
+
+// Renamed whenever the indexed tokens change, e.g. their positions,
+// so that an index built with the older tokens fails the schema check.
+const TOKENIZER: &str = "cjkbigram_v2";
+
+// Lowercased words of the titles, the texts and the tags, which are not
+// stemmed so that spelling suggestions, completions and patterns see
+// the words the user wrote, e.g. "connection" rather than "connect".
+const WORDS: &str = "words";
+const WORDS_TOKENIZER: &str = "words";
+
 #[macros::anyhow_to_string]
 fn build_schema(app_handle: Option<AppHandle>) -> anyhow::Result<()> {
     if let Some(_) = INITIALIZED.get() {
@@ -70,182 +331,932 @@ fn build_schema(app_handle: Option<AppHandle>) -> anyhow::Result<()> {
 
     let mut schema_builder = Schema::builder();
     schema_builder.add_text_field("id", STRING | STORED);
-    schema_builder.add_text_field("type", STORED);
-    schema_builder.add_text_field(
-        "text",
-        TextOptions::default().set_indexing_options(
+    schema_builder.add_text_field("type", STRING | STORED);
+    // Every field is stored so that documents can be rebuilt by `move_thread`.
+    let text_options = TextOptions::default()
+        .set_indexing_options(
             TextFieldIndexing::default()
-                .set_tokenizer("cjkbigram")
+                .set_tokenizer(TOKENIZER)
                 .set_index_option(IndexRecordOption::WithFreqsAndPositions),
-        ),
+        )
+        .set_stored();
+    schema_builder.add_text_field("title", text_options.clone());
+    schema_builder.add_text_field("text", text_options.clone());
+    schema_builder.add_text_field("tags", text_options);
+    let date_options = DateOptions::default()
+        .set_indexed()
+        .set_fast()
+        .set_stored()
+        .set_precision(DateTimePrecision::Milliseconds);
+    schema_builder.add_date_field(CREATED_AT, date_options.clone());
+    schema_builder.add_date_field(UPDATED_AT, date_options);
+    schema_builder.add_facet_field(threads::THREAD_PATH, FacetOptions::default().set_stored());
+    schema_builder.add_facet_field(facets::CATEGORIES, FacetOptions::default().set_stored());
+    schema_builder.add_text_field(OUTLINE, STRING | STORED | FAST);
+    let words_options = TextOptions::default().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer(WORDS_TOKENIZER)
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions),
     );
+    // Not stored: `move_thread` rebuilds the words from the other fields.
+    schema_builder.add_text_field(WORDS, words_options);
 
     let schema = schema_builder.build();
     let id_field = schema.get_field("id")?;
     let type_field = schema.get_field("type")?;
+    let title_field = schema.get_field("title")?;
     let text_field = schema.get_field("text")?;
+    let tags_field = schema.get_field("tags")?;
+    let created_at_field = schema.get_field(CREATED_AT)?;
+    let updated_at_field = schema.get_field(UPDATED_AT)?;
+    let thread_path_field = schema.get_field(threads::THREAD_PATH)?;
+    let categories_field = schema.get_field(facets::CATEGORIES)?;
+    let outline_field = schema.get_field(OUTLINE)?;
+    let words_field = schema.get_field(WORDS)?;
 
-    let index: Index = match app_handle {
-        Some(handle) => {
-            let app_dir = handle.path().app_data_dir()?;
-            let path = Path::join(&app_dir, "tantivy");
-            if !path.exists() {
-                fs::create_dir_all(path.clone())?;
-            }
-            let dir = ManagedDirectory::wrap(Box::new(MmapDirectory::open(path)?))?;
-            Index::open_or_create(dir, schema)
-        }
-        None => Ok(Index::create_in_ram(schema)),
-    }?;
+    let index_path = match &app_handle {
+        Some(handle) => Some(Path::join(&handle.path().app_data_dir()?, "tantivy")),
+        None => None,
+    };
+    let (index, recreated) = match &index_path {
+        Some(path) => recovery::open(path, schema)?,
+        None => (Index::create_in_ram(schema), None),
+    };
 
     let reader = index.reader()?;
-    let writer = index.writer(100_000_000)?;
+    let writer = match locking::open_writer(&index) {
+        Ok(writer) => Some(writer),
+        // Another process writes to the index. Writes are queued, and the
+        // writer is opened once that process releases the lock.
+        Err(e) if e.is::<IndexLockedError>() => None,
+        Err(e) => return Err(e),
+    };
 
     let tokenizer = TextAnalyzer::builder(CJKBigramTokenizer::new())
         .filter(Stemmer::new(Language::English))
         .filter(LowerCaser)
         .build();
-    index.tokenizers().register("cjkbigram", tokenizer);
+    index.tokenizers().register(TOKENIZER, tokenizer);
+    let words_tokenizer = TextAnalyzer::builder(CJKBigramTokenizer::new())
+        .filter(LowerCaser)
+        .build();
+    index
+        .tokenizers()
+        .register(WORDS_TOKENIZER, words_tokenizer);
 
-    let tokenizer_manager_for_query = TokenizerManager::new();
     let tokenizer_for_query = TextAnalyzer::builder(CJKBigramTokenizer::new().for_query())
         .filter(Stemmer::new(Language::English))
         .filter(LowerCaser)
         .build();
-    tokenizer_manager_for_query.register("cjkbigram", tokenizer_for_query);
 
-    let query_parser = Mutex::new(QueryParser::new(
-        index.schema(),
-        vec![text_field],
-        tokenizer_manager_for_query,
-    ));
+    if let Some(handle) = &app_handle {
+        synonyms::load(handle)?;
+        stop_words::load(handle)?;
+        field_boosts::load(handle)?;
+    }
 
     set_once_lock(&INDEX, index)?;
+    set_once_lock(&INDEX_PATH, index_path)?;
     set_once_lock(&READER, reader)?;
     set_once_lock(&WRITER, Mutex::new(writer))?;
     set_once_lock(&ID_FIELD, id_field)?;
     set_once_lock(&TYPE_FIELD, type_field)?;
+    set_once_lock(&TITLE_FIELD, title_field)?;
     set_once_lock(&TEXT_FIELD, text_field)?;
-    set_once_lock(&QUERY_PARSER, query_parser)?;
+    set_once_lock(&TAGS_FIELD, tags_field)?;
+    set_once_lock(&CREATED_AT_FIELD, created_at_field)?;
+    set_once_lock(&UPDATED_AT_FIELD, updated_at_field)?;
+    set_once_lock(&THREAD_PATH_FIELD, thread_path_field)?;
+    set_once_lock(&CATEGORIES_FIELD, categories_field)?;
+    set_once_lock(&OUTLINE_FIELD, outline_field)?;
+    set_once_lock(&WORDS_FIELD, words_field)?;
+    set_once_lock(&QUERY_TOKENIZER, tokenizer_for_query)?;
 
     set_once_lock(&INITIALIZED, ())?;
+
+    if let Some(handle) = app_handle {
+        if let Some(recreated) = recreated {
+            handle.emit(recovery::RECREATED_EVENT, recreated)?;
+        }
+        compaction::spawn_idle_job(move || {
+            // Writes queued while the index was locked are retried too.
+            let _ = apply_pending_writes();
+            // A failed compaction is retried on the next idle period.
+            if let Ok(Some(report)) = compact_if_needed() {
+                let _ = handle.emit(COMPACTED_EVENT, report);
+            }
+        });
+    }
     Ok(())
 }
 
+/// Writes made while another program holds the writer lock of the index
+/// are queued, and applied in order once the lock is released.
 #[tauri::command]
 #[specta::specta]
 #[macros::anyhow_to_string]
 pub async fn index(input: Vec<IndexTarget>) -> anyhow::Result<()> {
-    let mut writer = get_once_lock(&WRITER)?
-        .lock()
-        .map_err(|e| anyhow!(e.to_string()))?;
+    compaction::touch();
+    write(PendingWrite::Index(input))
+}
+
+fn add_documents(writer: &mut IndexWriter, input: Vec<IndexTarget>) -> anyhow::Result<()> {
     let id_field = get_once_lock(&ID_FIELD)?;
     let type_field = get_once_lock(&TYPE_FIELD)?;
+    let title_field = get_once_lock(&TITLE_FIELD)?;
     let text_field = get_once_lock(&TEXT_FIELD)?;
+    let tags_field = get_once_lock(&TAGS_FIELD)?;
+    let created_at_field = get_once_lock(&CREATED_AT_FIELD)?;
+    let updated_at_field = get_once_lock(&UPDATED_AT_FIELD)?;
+    let thread_path_field = get_once_lock(&THREAD_PATH_FIELD)?;
+    let categories_field = get_once_lock(&CATEGORIES_FIELD)?;
+    let outline_field = get_once_lock(&OUTLINE_FIELD)?;
+    let words_field = get_once_lock(&WORDS_FIELD)?;
 
     for item in input {
         let term = Term::from_field_text(*id_field, &item.id);
         writer.delete_term(term);
 
-        let text = remove_diacritics(item.text.nfc().collect::<String>().as_str());
+        let text = normalize(&item.text);
+        let tags = item.tags.unwrap_or_default();
+        let created_at = item.created_at.or_else(|| uuid_v7::timestamp(&item.id));
 
-        writer.add_document(doc!(
+        let mut document = doc!(
             *id_field => item.id,
-            *type_field => item.doc_type,
-            *text_field => text
-        ))?;
-    }
+            *type_field => item.doc_type.clone(),
+            *words_field => text.clone(),
+            *text_field => text,
+            *categories_field => facets::doc_type(&item.doc_type)
+        );
+        if let Some(title) = item.title {
+            let title = normalize(&title);
+            document.add_text(*words_field, &title);
+            document.add_text(*title_field, title);
+        }
+        for tag in tags {
+            let normalized = normalize(&tag);
+            document.add_text(*words_field, &normalized);
+            document.add_text(*tags_field, normalized);
+            document.add_facet(*categories_field, facets::tag(&tag));
+        }
+        if let Some(created_at) = created_at {
+            document.add_date(*created_at_field, to_date(created_at));
+        }
+        if let Some(updated_at) = item.updated_at.or(created_at) {
+            document.add_date(*updated_at_field, to_date(updated_at));
+        }
+        if let Some(thread_path) = item.thread_path.filter(|path| !path.is_empty()) {
+            document.add_facet(*thread_path_field, threads::facet(&thread_path));
+        }
+        if let Some(outline_path) = item.outline_path.filter(|path| !path.is_empty()) {
+            document.add_text(*outline_field, ranking::outline_key(&outline_path));
+        }
 
-    writer.commit()?;
+        writer.add_document(document)?;
+    }
     Ok(())
 }
 
+/// Moves the thread under the thread at `parent_path`, an empty path
+/// meaning the root. Its cards and descendant threads are moved along.
+/// `outline_path` is the new outline path of the moved thread.
+/// Like `index`, the move is queued while the index is locked.
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn move_thread(
+    thread_id: String,
+    parent_path: Vec<String>,
+    outline_path: Vec<String>,
+) -> anyhow::Result<()> {
+    compaction::touch();
+    write(PendingWrite::MoveThread {
+        thread_id,
+        parent_path,
+        outline_path,
+    })
+}
+
+fn move_subtree(
+    writer: &mut IndexWriter,
+    thread_id: String,
+    parent_path: Vec<String>,
+    outline_path: Vec<String>,
+) -> anyhow::Result<()> {
+    let reader = get_once_lock(&READER)?;
+    let id_field = get_once_lock(&ID_FIELD)?;
+    let thread_path_field = get_once_lock(&THREAD_PATH_FIELD)?;
+    let fields = threads::Fields {
+        id: *id_field,
+        thread_path: *thread_path_field,
+        outline: *get_once_lock(&OUTLINE_FIELD)?,
+        title: *get_once_lock(&TITLE_FIELD)?,
+        text: *get_once_lock(&TEXT_FIELD)?,
+        tags: *get_once_lock(&TAGS_FIELD)?,
+        words: *get_once_lock(&WORDS_FIELD)?,
+    };
+
+    // Commits made before taking the lock must be visible.
+    reader.reload()?;
+    let searcher = reader.searcher();
+    let old_path = threads::find_path(&searcher, *id_field, *thread_path_field, &thread_id)?
+        .ok_or(anyhow!("The thread is not indexed"))?;
+    let mut new_path = parent_path;
+    new_path.push(thread_id);
+
+    threads::move_subtree(
+        &searcher,
+        writer,
+        &fields,
+        &old_path,
+        &threads::facet(&new_path),
+        &outline_path,
+    )
+}
+
 #[tauri::command]
 #[specta::specta]
 #[macros::anyhow_to_string]
 pub async fn search(
-    query: &str,
-    levenshtein_distance: u8,
+    query: QueryNode,
+    session_id: Option<String>,
+    options: SearchOptions,
+) -> anyhow::Result<SearchResponse> {
+    compaction::touch();
+    let mut results: Vec<SearchResult> = vec![];
+    let session = SearchSession::start(session_id)?;
+
+    let searcher = get_once_lock(&READER)?.searcher();
+
+    let query_builder = query_builder(&searcher, options.mode, options.fuzziness)?;
+    let query_parsed = match query_builder.build(&query)? {
+        Some(query) => query,
+        None => {
+            return Ok(SearchResponse {
+                results,
+                groups: None,
+                facets: None,
+            })
+        }
+    };
+
+    session.check()?;
+
+    let query_parsed = query_parsed.as_ref();
+    let top_docs = TopDocs::with_limit(options.limit as usize);
+    let facet_collector = options
+        .facets
+        .then(|| facets::collector(threads::THREAD_PATH));
+
+    if let Some(hits_per_thread) = options.hits_per_thread {
+        let collector = ThreadGroupCollector {
+            thread_path_field: threads::THREAD_PATH.to_string(),
+            hits_per_thread: hits_per_thread as usize,
+        };
+        let (groups, facets) = collect(
+            &searcher,
+            query_parsed,
+            &session,
+            collector,
+            facet_collector,
+        )?;
+        let mut groups = groups.into_iter().collect::<Vec<_>>();
+        groups.sort_by(|a, b| b.1.score.total_cmp(&a.1.score).then_with(|| a.0.cmp(&b.0)));
+        groups.truncate(options.limit as usize);
+
+        let mut thread_groups = vec![];
+        for (thread_id, group) in groups {
+            let mut hits = vec![];
+            for (_, doc_address) in group.hits {
+                session.check()?;
+                hits.push(to_result(&searcher, doc_address)?);
+            }
+            thread_groups.push(ThreadGroup {
+                thread_id,
+                hit_count: group.hit_count,
+                hits,
+            });
+        }
+
+        return Ok(SearchResponse {
+            results,
+            groups: Some(thread_groups),
+            facets,
+        });
+    }
+
+    let (doc_addresses, facets) = match (options.sort_by, options.recency_boost) {
+        (SortBy::Relevance, None) => {
+            collect_top_docs(&searcher, query_parsed, &session, top_docs, facet_collector)?
+        }
+        (SortBy::Relevance, Some(boost)) => collect_top_docs(
+            &searcher,
+            query_parsed,
+            &session,
+            top_docs.tweak_score(ranking::recency_tweaker(boost)),
+            facet_collector,
+        )?,
+        (SortBy::CreatedAt, _) => collect_top_docs(
+            &searcher,
+            query_parsed,
+            &session,
+            top_docs.tweak_score(ranking::date_tweaker(CREATED_AT)),
+            facet_collector,
+        )?,
+        (SortBy::UpdatedAt, _) => collect_top_docs(
+            &searcher,
+            query_parsed,
+            &session,
+            top_docs.tweak_score(ranking::date_tweaker(UPDATED_AT)),
+            facet_collector,
+        )?,
+        (SortBy::Outline, _) => collect_top_docs(
+            &searcher,
+            query_parsed,
+            &session,
+            top_docs.tweak_score(ranking::outline_tweaker()),
+            facet_collector,
+        )?,
+    };
+
+    for doc_addres in doc_addresses {
+        session.check()?;
+        results.push(to_result(&searcher, doc_addres)?);
+    }
+
+    Ok(SearchResponse {
+        results,
+        groups: None,
+        facets,
+    })
+}
+
+/// Returns the cards most similar to the given card, excluding the cards of
+/// its own thread when `exclude_thread` is set.
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn related(
+    id: String,
     limit: u8,
+    exclude_thread: bool,
 ) -> anyhow::Result<Vec<SearchResult>> {
-    let mut results: Vec<SearchResult> = vec![];
-    let query = remove_diacritics(query.nfc().collect::<String>().as_str());
+    let mut results = vec![];
+    if limit == 0 {
+        return Ok(results);
+    }
+
+    let searcher = get_once_lock(&READER)?.searcher();
+    let fields = search_fields()?;
+    let document =
+        find_document(&searcher, fields.id, &id)?.ok_or(anyhow!("The card is not indexed"))?;
+
+    let query = related::query(&document, &id, &fields, exclude_thread);
+    let top_docs = searcher.search(&query, &TopDocs::with_limit(limit as usize))?;
 
+    for (_, doc_address) in top_docs {
+        results.push(to_result(&searcher, doc_address)?);
+    }
+    Ok(results)
+}
+
+// Searches with fewer hits than this get a spelling suggestion.
+const FEW_HITS: usize = 3;
+
+/// Returns the text with its misspelled words replaced by the most frequent
+/// similar words of the index, when the text has few hits with the options
+/// and the replaced text has more. Suggested words are taken from the
+/// dictionary of the index, so they are lowercased, e.g. "Conection" gives
+/// "connection".
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn suggest(text: String, options: SearchOptions) -> anyhow::Result<Option<String>> {
+    let searcher = get_once_lock(&READER)?.searcher();
+    let query_builder = query_builder(&searcher, options.mode, options.fuzziness)?;
+    let words_field = get_once_lock(&WORDS_FIELD)?;
+    let mut analyzer = get_once_lock(&INDEX)?
+        .tokenizers()
+        .get(WORDS_TOKENIZER)
+        .ok_or(anyhow!("The words tokenizer is not registered"))?;
+
+    let count = |text: &str| -> anyhow::Result<usize> {
+        let node = QueryNode::Text {
+            text: text.to_string(),
+        };
+        match query_builder.build(&node)? {
+            Some(query) => Ok(searcher.search(&query, &Count)?),
+            None => Ok(0),
+        }
+    };
+    let hits = count(&text)?;
+    if hits >= FEW_HITS {
+        return Ok(None);
+    }
+
+    let mut words = vec![];
+    for word in text.split_whitespace() {
+        // Query syntax and CJK bigrams are left as they are.
+        let mut tokens = vec![];
+        if word.chars().all(char::is_alphanumeric) {
+            let normalized = normalize(word);
+            let mut token_stream = analyzer.token_stream(&normalized);
+            token_stream.process(&mut |token| tokens.push(token.text.clone()));
+        }
+        let correction = match tokens.as_slice() {
+            [token] if !spelling::is_indexed(&searcher, *words_field, token)? => {
+                let distance = Fuzziness::Auto.distance(token);
+                spelling::correct(&searcher, *words_field, token, distance)?
+            }
+            _ => None,
+        };
+        words.push(correction.unwrap_or_else(|| word.to_string()));
+    }
+
+    let suggestion = words.join(" ");
+    if suggestion == text.split_whitespace().collect::<Vec<&str>>().join(" ")
+        || count(&suggestion)? <= hits
+    {
+        return Ok(None);
+    }
+    Ok(Some(suggestion))
+}
+
+/// Completes the text being typed in the search box with the titles of the
+/// threads matching it, then with the most frequent words of the index.
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn autocomplete(prefix: String, limit: u8) -> anyhow::Result<Vec<Completion>> {
     let searcher = get_once_lock(&READER)?.searcher();
     let id_field = get_once_lock(&ID_FIELD)?;
-    let type_field = get_once_lock(&TYPE_FIELD)?;
-    let text_field = get_once_lock(&TEXT_FIELD)?;
+    let title_field = get_once_lock(&TITLE_FIELD)?;
+    let words_field = get_once_lock(&WORDS_FIELD)?;
+    let limit = limit as usize;
+    let mut completions = vec![];
+    // Nothing is completed once the last word is followed by a space.
+    if limit == 0 || !prefix.ends_with(char::is_alphanumeric) {
+        return Ok(completions);
+    }
 
-    let mut query_parser = get_once_lock(&QUERY_PARSER)?
-        .lock()
-        .map_err(|e| anyhow!(e.to_string()))?;
-    query_parser.set_field_fuzzy(*text_field, true, levenshtein_distance, true);
-    query_parser.set_conjunction_by_default();
-
-    let query_parsed = query_parser.parse_query(&query)?;
-
-    let top_docs = searcher.search(&query_parsed, &TopDocs::with_limit(limit as usize))?;
-
-    for (_, doc_addres) in top_docs {
-        let retreived_doc = searcher.doc::<TantivyDocument>(doc_addres)?;
-        let id_value = retreived_doc
-            .get_first(*id_field)
-            .ok_or(anyhow!("id field of the search result is not defined!"))?
-            .as_str()
-            .ok_or(anyhow!("id field of the search result is not defined!"))?;
-
-        let type_value = retreived_doc
-            .get_first(*type_field)
-            .ok_or(anyhow!("type field of the search result is not defined!"))?
-            .as_str()
-            .ok_or(anyhow!("type field of the search result is not defined!"))?;
-
-        results.push(SearchResult {
-            id: id_value.to_string(),
-            doc_type: type_value.to_string(),
+    let query_builder = query_builder(
+        &searcher,
+        SearchMode::Incremental,
+        Fuzziness::Fixed { distance: 0 },
+    )?;
+    let node = QueryNode::Bool {
+        must: vec![QueryNode::Prefix {
+            text: prefix.clone(),
+            field: Some(QueryField::Title),
+        }],
+        should: vec![],
+        must_not: vec![],
+        filter: vec![QueryNode::DocType {
+            doc_type: String::from("thread"),
+        }],
+    };
+    if let Some(query) = query_builder.build(&node)? {
+        for (_, doc_address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+            let document = searcher.doc::<TantivyDocument>(doc_address)?;
+            let title = document
+                .get_first(*title_field)
+                .and_then(|value| value.as_str());
+            let id = document
+                .get_first(*id_field)
+                .and_then(|value| value.as_str());
+            if let (Some(title), Some(id)) = (title, id) {
+                completions.push(Completion {
+                    text: title.to_string(),
+                    thread_id: Some(id.to_string()),
+                });
+            }
+        }
+    }
+
+    let terms =
+        autocomplete::complete_terms(&searcher, *words_field, &prefix, limit - completions.len())?;
+    for text in terms {
+        completions.push(Completion {
+            text,
+            thread_id: None,
         });
     }
 
-    Ok(results)
+    Ok(completions)
+}
+
+/// Returns the document counts and the storage usage of the index.
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn index_stats() -> anyhow::Result<IndexStats> {
+    let reader = get_once_lock(&READER)?;
+    // Commits made right before must be counted.
+    reader.reload()?;
+    let searcher = reader.searcher();
+    let counts = searcher.search(&AllQuery, &facets::collector(threads::THREAD_PATH))?;
+
+    let size_in_bytes = match get_once_lock(&INDEX_PATH)? {
+        Some(path) => Some(stats::directory_size(path)? as f64),
+        None => None,
+    };
+
+    Ok(IndexStats {
+        doc_types: facets::counts(counts).doc_types,
+        deleted_docs: searcher
+            .segment_readers()
+            .iter()
+            .map(|segment_reader| segment_reader.num_deleted_docs())
+            .sum(),
+        segments: searcher.segment_readers().len() as u32,
+        size_in_bytes,
+        last_commit_at: stats::last_commit_at(get_once_lock(&INDEX)?)?,
+    })
+}
+
+/// Merges the segments of the index, purging the documents deleted by
+/// updates, and deletes the files the index no longer uses.
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn compact() -> anyhow::Result<CompactionReport> {
+    compaction::compact(
+        get_once_lock(&INDEX)?,
+        get_once_lock(&INDEX_PATH)?,
+        get_once_lock(&READER)?,
+        lock_writer,
+    )
+}
+
+// Run by the background job, which skips indexes with little to reclaim.
+fn compact_if_needed() -> anyhow::Result<Option<CompactionReport>> {
+    let reader = get_once_lock(&READER)?;
+    reader.reload()?;
+    if !compaction::needs_compaction(&reader.searcher()) {
+        return Ok(None);
+    }
+
+    Ok(Some(compaction::compact(
+        get_once_lock(&INDEX)?,
+        get_once_lock(&INDEX_PATH)?,
+        reader,
+        lock_writer,
+    )?))
+}
+
+/// Runs the text through the normalization and the analyzer used for
+/// indexing or searching, to find out why a note is not found.
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn analyze(text: String, mode: AnalyzerMode) -> anyhow::Result<Analysis> {
+    let mut analyzer = match mode {
+        AnalyzerMode::Index => get_once_lock(&INDEX)?
+            .tokenizers()
+            .get(TOKENIZER)
+            .ok_or(anyhow!("The index tokenizer is not registered"))?,
+        AnalyzerMode::Query => get_once_lock(&QUERY_TOKENIZER)?.clone(),
+    };
+
+    let normalized = normalize(&text);
+    let mut tokens = vec![];
+    analyzer.token_stream(&normalized).process(&mut |token| {
+        tokens.push(AnalyzedToken {
+            text: token.text.clone(),
+            offset_from: token.offset_from as u32,
+            offset_to: token.offset_to as u32,
+            position: token.position as u32,
+        });
+    });
+
+    Ok(Analysis { normalized, tokens })
+}
+
+/// Explains how the query matches the document, to diagnose rankings from
+/// the devtools. `explanation` is tantivy's `Explanation` of the relevance
+/// score as JSON, and is `None` when the document doesn't match.
+/// Sort orders and the recency boost are applied on top of this score.
+#[tauri::command]
+#[specta::specta]
+#[macros::anyhow_to_string]
+pub async fn explain(
+    query: QueryNode,
+    id: String,
+    options: SearchOptions,
+) -> anyhow::Result<SearchExplanation> {
+    let searcher = get_once_lock(&READER)?.searcher();
+    let id_field = get_once_lock(&ID_FIELD)?;
+    let doc_address =
+        find_address(&searcher, *id_field, &id)?.ok_or(anyhow!("The document is not indexed"))?;
+
+    let query_builder = query_builder(&searcher, options.mode, options.fuzziness)?;
+    let tree = query_builder.expand(&query)?;
+    let tokens = query_builder.tokens(&tree)?;
+
+    let explanation = match query_builder.build(&tree)? {
+        Some(query) => match query.explain(&searcher, doc_address) {
+            Ok(explanation) => Some(explanation),
+            // the document doesn't match
+            Err(TantivyError::InvalidArgument(_)) => None,
+            Err(e) => return Err(e.into()),
+        },
+        None => None,
+    };
+
+    Ok(SearchExplanation {
+        tree,
+        tokens,
+        score: explanation.as_ref().map(|explanation| explanation.value()),
+        explanation: explanation.map(|explanation| explanation.to_pretty_json()),
+    })
+}
+
+fn query_builder(
+    searcher: &Searcher,
+    mode: SearchMode,
+    fuzziness: Fuzziness,
+) -> anyhow::Result<QueryBuilder> {
+    Ok(QueryBuilder {
+        searcher: searcher.clone(),
+        fields: search_fields()?,
+        boosts: field_boosts::get()?,
+        synonyms: synonyms::get()?,
+        stop_words: stop_words::current()?,
+        analyzer: get_once_lock(&QUERY_TOKENIZER)?.clone(),
+        mode,
+        fuzziness,
+    })
+}
+
+fn search_fields() -> anyhow::Result<SearchFields> {
+    Ok(SearchFields {
+        id: *get_once_lock(&ID_FIELD)?,
+        title: *get_once_lock(&TITLE_FIELD)?,
+        text: *get_once_lock(&TEXT_FIELD)?,
+        tags: *get_once_lock(&TAGS_FIELD)?,
+        doc_type: *get_once_lock(&TYPE_FIELD)?,
+        thread_path: *get_once_lock(&THREAD_PATH_FIELD)?,
+        words: *get_once_lock(&WORDS_FIELD)?,
+    })
+}
+
+fn find_address(
+    searcher: &Searcher,
+    id_field: Field,
+    id: &str,
+) -> anyhow::Result<Option<DocAddress>> {
+    let query = TermQuery::new(
+        Term::from_field_text(id_field, id),
+        IndexRecordOption::Basic,
+    );
+    Ok(searcher
+        .search(&query, &TopDocs::with_limit(1))?
+        .pop()
+        .map(|(_, doc_address)| doc_address))
+}
+
+fn find_document(
+    searcher: &Searcher,
+    id_field: Field,
+    id: &str,
+) -> anyhow::Result<Option<TantivyDocument>> {
+    match find_address(searcher, id_field, id)? {
+        Some(doc_address) => Ok(Some(searcher.doc::<TantivyDocument>(doc_address)?)),
+        None => Ok(None),
+    }
+}
+
+fn to_result(searcher: &Searcher, doc_address: DocAddress) -> anyhow::Result<SearchResult> {
+    let id_field = get_once_lock(&ID_FIELD)?;
+    let type_field = get_once_lock(&TYPE_FIELD)?;
+
+    let retreived_doc = searcher.doc::<TantivyDocument>(doc_address)?;
+    let id_value = retreived_doc
+        .get_first(*id_field)
+        .ok_or(anyhow!("id field of the search result is not defined!"))?
+        .as_str()
+        .ok_or(anyhow!("id field of the search result is not defined!"))?;
+
+    let type_value = retreived_doc
+        .get_first(*type_field)
+        .ok_or(anyhow!("type field of the search result is not defined!"))?
+        .as_str()
+        .ok_or(anyhow!("type field of the search result is not defined!"))?;
+
+    Ok(SearchResult {
+        id: id_value.to_string(),
+        doc_type: type_value.to_string(),
+    })
+}
+
+// Runs the search, telling a cancellation apart from other failures.
+// Facets are counted in the same pass as the hits are collected.
+fn collect<C: Collector>(
+    searcher: &Searcher,
+    query: &dyn Query,
+    session: &SearchSession,
+    collector: C,
+    facet_collector: Option<(FacetCollector, FacetCollector)>,
+) -> anyhow::Result<(C::Fruit, Option<Facets>)> {
+    match searcher.search(query, &session.collector((collector, facet_collector))) {
+        Ok((fruit, facet_counts)) => Ok((fruit, facet_counts.map(facets::counts))),
+        Err(e) => {
+            session.check()?;
+            Err(e.into())
+        }
+    }
+}
+
+fn collect_top_docs<T, C>(
+    searcher: &Searcher,
+    query: &dyn Query,
+    session: &SearchSession,
+    top_docs: C,
+    facet_collector: Option<(FacetCollector, FacetCollector)>,
+) -> anyhow::Result<(Vec<DocAddress>, Option<Facets>)>
+where
+    C: Collector<Fruit = Vec<(T, DocAddress)>>,
+{
+    let (top_docs, facets) = collect(searcher, query, session, top_docs, facet_collector)?;
+    let doc_addresses = top_docs.into_iter().map(|(_, address)| address).collect();
+    Ok((doc_addresses, facets))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use query::DateField;
+    use stop_words::StopWords;
 
-    #[test]
-    fn test() {
-        let _ = build_schema(None);
+    fn text(query: &str) -> QueryNode {
+        QueryNode::Text {
+            text: String::from(query),
+        }
+    }
 
-        let input = vec![
-            IndexTarget {
-                id: String::from("1"),
-                doc_type: String::from("card"),
-                text: String::from("content brûlée connection"),
-            },
-            IndexTarget {
-                id: String::from("2"),
-                doc_type: String::from("thread"),
-                text: String::from("東京国際空港（とうきょうこくさいくうこう、英語: Tokyo International Airport）は、東京都大田区にある日本最大の空港。通称は羽田空港（はねだくうこう、英語: Haneda Airport）であり、単に「羽田」と呼ばれる場合もある。空港コードはHND。"),
-            },
-            IndexTarget {
-                id: String::from("3"),
-                doc_type: String::from("thread"),
-                text: String::from("股份有限公司"),
-            },
-            IndexTarget {
-                id: String::from("4"),
-                doc_type: String::from("card"),
-                text: String::from("デカすぎで草"),
-            },
-        ];
+    fn options(mode: SearchMode, fuzziness: Fuzziness) -> SearchOptions {
+        SearchOptions {
+            mode,
+            fuzziness,
+            limit: 100,
+            sort_by: SortBy::Relevance,
+            recency_boost: None,
+            facets: false,
+            hits_per_thread: None,
+        }
+    }
 
-        let _ = index(input);
-        READER.get().unwrap().reload().unwrap();
+    fn ids(response: SearchResponse) -> Vec<String> {
+        response
+            .results
+            .into_iter()
+            .map(|result| result.id)
+            .collect()
+    }
+
+    const DAY: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+    // Time at which the documents shared by the tests were indexed.
+    static NOW: OnceLock<f64> = OnceLock::new();
+
+    // Held by the tests which write to the shared index, so that they do not
+    // see the segments and deleted documents of each other.
+    static WRITES: Mutex<()> = Mutex::new(());
+
+    fn target(id: &str, doc_type: &str, text: &str) -> IndexTarget {
+        IndexTarget {
+            id: String::from(id),
+            doc_type: String::from(doc_type),
+            title: None,
+            text: String::from(text),
+            tags: None,
+            created_at: None,
+            updated_at: None,
+            thread_path: None,
+            outline_path: None,
+        }
+    }
+
+    fn path(ids: &[&str]) -> Option<Vec<String>> {
+        Some(ids.iter().map(|id| String::from(*id)).collect())
+    }
+
+    // Indexes the shared documents once and returns the time they were
+    // indexed at.
+    fn setup() -> f64 {
+        *NOW.get_or_init(|| {
+            build_schema(None).unwrap();
+            let now = ranking::now_in_millis() as f64;
+
+            let input = vec![
+                target("1", "card", "content brûlée connection"),
+                target("2", "thread", "東京国際空港（とうきょうこくさいくうこう、英語: Tokyo International Airport）は、東京都大田区にある日本最大の空港。通称は羽田空港（はねだくうこう、英語: Haneda Airport）であり、単に「羽田」と呼ばれる場合もある。空港コードはHND。"),
+                target("3", "thread", "股份有限公司"),
+                target("4", "card", "デカすぎで草"),
+                IndexTarget {
+                    title: Some(String::from("Kubernetes")),
+                    tags: Some(vec![String::from("infra")]),
+                    ..target("5", "thread", "orchestration notes")
+                },
+                target("6", "card", "kubernetes upgrade"),
+                IndexTarget {
+                    created_at: Some(now - 30.0 * DAY),
+                    ..target("7", "card", "milestone planning")
+                },
+                IndexTarget {
+                    created_at: Some(now - 60.0 * DAY),
+                    updated_at: Some(now - DAY),
+                    ..target("8", "card", "milestone review")
+                },
+                IndexTarget {
+                    thread_path: path(&["9"]),
+                    outline_path: path(&["a0"]),
+                    ..target("9", "thread", "roadmap overview")
+                },
+                IndexTarget {
+                    thread_path: path(&["9", "11"]),
+                    outline_path: path(&["a0", "a1", "a0"]),
+                    ..target("10", "card", "roadmap draft")
+                },
+                IndexTarget {
+                    thread_path: path(&["9", "11"]),
+                    outline_path: path(&["a0", "a1"]),
+                    ..target("11", "thread", "roadmap details")
+                },
+                IndexTarget {
+                    thread_path: path(&["12"]),
+                    outline_path: path(&["a1"]),
+                    ..target("12", "thread", "roadmap archive")
+                },
+                IndexTarget {
+                    thread_path: path(&["12"]),
+                    ..target("13", "card", "deploy pipeline rollout")
+                },
+                IndexTarget {
+                    thread_path: path(&["12"]),
+                    ..target("14", "card", "deploy pipeline failure")
+                },
+                IndexTarget {
+                    thread_path: path(&["9"]),
+                    ..target("15", "card", "deploy pipeline handbook")
+                },
+                IndexTarget {
+                    thread_path: path(&["9"]),
+                    ..target("16", "card", "garden tomatoes")
+                },
+                target("17", "card", "TODO(alice) rotate keys"),
+                // moved by `moved_thread`
+                IndexTarget {
+                    thread_path: path(&["20"]),
+                    outline_path: path(&["b0"]),
+                    ..target("20", "thread", "itinerary overview")
+                },
+                IndexTarget {
+                    thread_path: path(&["20", "22"]),
+                    outline_path: path(&["b0", "b1", "b0"]),
+                    ..target("21", "card", "itinerary draft")
+                },
+                IndexTarget {
+                    thread_path: path(&["20", "22"]),
+                    outline_path: path(&["b0", "b1"]),
+                    ..target("22", "thread", "itinerary layovers")
+                },
+                IndexTarget {
+                    thread_path: path(&["23"]),
+                    outline_path: path(&["b1"]),
+                    ..target("23", "thread", "itinerary archive")
+                },
+            ];
+
+            index(input).unwrap();
+            READER.get().unwrap().reload().unwrap();
+            now
+        })
+    }
+
+    fn standard() -> SearchOptions {
+        options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+    }
+
+    fn sorted_ids(response: SearchResponse) -> Vec<String> {
+        let mut sorted = ids(response);
+        sorted.sort();
+        sorted
+    }
+
+    #[test]
+    fn test() {
+        setup();
 
         // prefix search
         assert_eq!(
-            search("c", 0, 100).unwrap(),
+            search(
+                text("c"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
             vec![SearchResult {
                 id: String::from("1"),
                 doc_type: String::from("card")
@@ -254,7 +1265,13 @@ mod tests {
 
         // remove diacritics
         assert_eq!(
-            search("brulee", 0, 100).unwrap(),
+            search(
+                text("brulee"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
             vec![SearchResult {
                 id: String::from("1"),
                 doc_type: String::from("card")
@@ -263,7 +1280,13 @@ mod tests {
 
         // NFC normalization
         assert_eq!(
-            search("brûlée".nfd().collect::<String>().as_str(), 0, 100).unwrap(),
+            search(
+                text("brûlée".nfd().collect::<String>().as_str()),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
             vec![SearchResult {
                 id: String::from("1"),
                 doc_type: String::from("card")
@@ -272,7 +1295,13 @@ mod tests {
 
         // english stemming
         assert_eq!(
-            search("connected", 0, 100).unwrap(),
+            search(
+                text("connected"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
             vec![SearchResult {
                 id: String::from("1"),
                 doc_type: String::from("card")
@@ -281,16 +1310,65 @@ mod tests {
 
         // fuzzy search
         assert_eq!(
-            search("cantnt", 2, 100).unwrap(),
+            search(
+                text("cantnt"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 2 })
+            )
+            .unwrap()
+            .results,
             vec![SearchResult {
                 id: String::from("1"),
                 doc_type: String::from("card")
             },]
         );
 
+        // length-aware fuzziness
+        assert_eq!(
+            search(
+                text("cantnt"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Auto)
+            )
+            .unwrap()
+            .results,
+            vec![SearchResult {
+                id: String::from("1"),
+                doc_type: String::from("card")
+            },]
+        );
+        assert_eq!(
+            search(
+                text("cx"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Auto)
+            )
+            .unwrap()
+            .results,
+            vec![]
+        );
+
+        // no fuzziness for CJK
+        assert_eq!(
+            search(
+                text("羽由"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 1 })
+            )
+            .unwrap()
+            .results,
+            vec![]
+        );
+
         // japanese bigram
         assert_eq!(
-            search("はねだ", 0, 100).unwrap(),
+            search(
+                text("はねだ"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
             vec![SearchResult {
                 id: String::from("2"),
                 doc_type: String::from("thread")
@@ -299,7 +1377,13 @@ mod tests {
 
         // english and japanese compound
         assert_eq!(
-            search("羽田Airport", 0, 100).unwrap(),
+            search(
+                text("羽田Airport"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
             vec![SearchResult {
                 id: String::from("2"),
                 doc_type: String::from("thread")
@@ -308,7 +1392,13 @@ mod tests {
 
         // lowercase
         assert_eq!(
-            search("hnd", 0, 100).unwrap(),
+            search(
+                text("hnd"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
             vec![SearchResult {
                 id: String::from("2"),
                 doc_type: String::from("thread")
@@ -317,7 +1407,13 @@ mod tests {
 
         // chinese bigram
         assert_eq!(
-            search("份有", 0, 100).unwrap(),
+            search(
+                text("份有"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
             vec![SearchResult {
                 id: String::from("3"),
                 doc_type: String::from("thread")
@@ -326,11 +1422,768 @@ mod tests {
 
         // search one character word on the end of the sentence
         assert_eq!(
-            search("草", 0, 100).unwrap(),
+            search(
+                text("草"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
             vec![SearchResult {
                 id: String::from("4"),
                 doc_type: String::from("card")
             },]
         );
+
+        // doc type filter
+        assert_eq!(
+            search(
+                text("airport type:thread"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
+            vec![SearchResult {
+                id: String::from("2"),
+                doc_type: String::from("thread")
+            },]
+        );
+        assert_eq!(
+            search(
+                text("airport -type:thread"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
+            vec![]
+        );
+
+        // title matches rank higher
+        assert_eq!(
+            search(
+                text("kubernetes"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
+            vec![
+                SearchResult {
+                    id: String::from("5"),
+                    doc_type: String::from("thread")
+                },
+                SearchResult {
+                    id: String::from("6"),
+                    doc_type: String::from("card")
+                },
+            ]
+        );
+
+        // field scoped queries
+        assert_eq!(
+            search(
+                text("title:kubernetes"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
+            vec![SearchResult {
+                id: String::from("5"),
+                doc_type: String::from("thread")
+            },]
+        );
+        assert_eq!(
+            search(
+                text("tags:infra"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
+            vec![SearchResult {
+                id: String::from("5"),
+                doc_type: String::from("thread")
+            },]
+        );
+
+        // search as you type
+        assert_eq!(
+            search(
+                text("content conne"),
+                None,
+                options(SearchMode::Incremental, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
+            vec![SearchResult {
+                id: String::from("1"),
+                doc_type: String::from("card")
+            },]
+        );
+        assert_eq!(
+            search(
+                text("conte conne"),
+                None,
+                options(SearchMode::Incremental, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
+            vec![]
+        );
+        assert_eq!(
+            search(
+                text("c"),
+                None,
+                options(SearchMode::Incremental, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
+            vec![]
+        );
+
+        // stray query syntax is searched as plain words
+        assert_eq!(
+            search(
+                text("content: (\"brûlée"),
+                None,
+                options(SearchMode::Standard, Fuzziness::Fixed { distance: 0 })
+            )
+            .unwrap()
+            .results,
+            vec![SearchResult {
+                id: String::from("1"),
+                doc_type: String::from("card")
+            },]
+        );
+
+        // concurrent searches with different settings
+        let cases = [
+            (
+                "c",
+                SearchMode::Standard,
+                Fuzziness::Fixed { distance: 0 },
+                vec!["1"],
+            ),
+            (
+                "c",
+                SearchMode::Incremental,
+                Fuzziness::Fixed { distance: 0 },
+                vec![],
+            ),
+            (
+                "cantnt",
+                SearchMode::Standard,
+                Fuzziness::Fixed { distance: 2 },
+                vec!["1"],
+            ),
+            (
+                "cantnt",
+                SearchMode::Standard,
+                Fuzziness::Fixed { distance: 0 },
+                vec![],
+            ),
+            (
+                "はねだ",
+                SearchMode::Incremental,
+                Fuzziness::Auto,
+                vec!["2"],
+            ),
+        ];
+        let handles = (0..100)
+            .map(|i| {
+                let (query, mode, fuzziness, expected) = cases[i % cases.len()].clone();
+                std::thread::spawn(move || {
+                    let found = ids(search(text(query), None, options(mode, fuzziness)).unwrap());
+                    assert_eq!(found, expected);
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn dates() {
+        let now = setup();
+
+        // date range filters
+        let milestones_since = |field: DateField, days: f64| QueryNode::Bool {
+            must: vec![text("milestone")],
+            should: vec![],
+            must_not: vec![],
+            filter: vec![QueryNode::DateRange {
+                field,
+                from: Some(now - days * DAY),
+                to: None,
+            }],
+        };
+        assert_eq!(
+            ids(search(
+                milestones_since(DateField::UpdatedAt, 7.0),
+                None,
+                standard()
+            )
+            .unwrap()),
+            vec!["8"]
+        );
+        assert_eq!(
+            ids(search(
+                milestones_since(DateField::CreatedAt, 45.0),
+                None,
+                standard()
+            )
+            .unwrap()),
+            vec!["7"]
+        );
+        assert_eq!(
+            ids(search(
+                milestones_since(DateField::CreatedAt, 7.0),
+                None,
+                standard()
+            )
+            .unwrap()),
+            Vec::<String>::new()
+        );
+
+        // sort by date
+        let sorted_by = |sort_by: SortBy| SearchOptions {
+            sort_by,
+            ..standard()
+        };
+        assert_eq!(
+            ids(search(text("milestone"), None, sorted_by(SortBy::CreatedAt)).unwrap()),
+            vec!["7", "8"]
+        );
+        assert_eq!(
+            ids(search(text("milestone"), None, sorted_by(SortBy::UpdatedAt)).unwrap()),
+            vec!["8", "7"]
+        );
+
+        // recently updated documents rank higher
+        let recency_boost = SearchOptions {
+            recency_boost: Some(RecencyBoost {
+                weight: 1.0,
+                half_life_days: 7.0,
+            }),
+            ..standard()
+        };
+        assert_eq!(
+            ids(search(text("milestone"), None, recency_boost).unwrap()),
+            vec!["8", "7"]
+        );
+    }
+
+    #[test]
+    fn threads() {
+        setup();
+        let in_thread = |query: &str| sorted_ids(search(text(query), None, standard()).unwrap());
+
+        // thread subtree
+        assert_eq!(in_thread("roadmap thread:9"), vec!["10", "11", "9"]);
+        assert_eq!(in_thread("roadmap thread:11"), vec!["10", "11"]);
+        assert_eq!(in_thread("roadmap thread:unknown"), Vec::<String>::new());
+
+        // outline order
+        let in_outline = SearchOptions {
+            sort_by: SortBy::Outline,
+            ..standard()
+        };
+        assert_eq!(
+            ids(search(text("roadmap"), None, in_outline).unwrap()),
+            vec!["9", "11", "10", "12"]
+        );
+    }
+
+    #[test]
+    fn moved_thread() {
+        setup();
+        let _writes = WRITES.lock().unwrap();
+        let in_thread = |query: &str| sorted_ids(search(text(query), None, standard()).unwrap());
+        let in_outline = SearchOptions {
+            sort_by: SortBy::Outline,
+            ..standard()
+        };
+        assert_eq!(
+            ids(search(text("itinerary"), None, in_outline).unwrap()),
+            vec!["20", "22", "21", "23"]
+        );
+
+        // the subtree follows a moved thread
+        move_thread(
+            String::from("22"),
+            vec![String::from("23")],
+            vec![String::from("b1"), String::from("b0")],
+        )
+        .unwrap();
+        READER.get().unwrap().reload().unwrap();
+        assert_eq!(in_thread("itinerary thread:20"), vec!["20"]);
+        assert_eq!(in_thread("itinerary thread:23"), vec!["21", "22", "23"]);
+        assert_eq!(in_thread("draft thread:22"), vec!["21"]);
+        assert_eq!(
+            ids(search(text("itinerary"), None, in_outline).unwrap()),
+            vec!["20", "23", "22", "21"]
+        );
+
+        // the words of the moved documents are rebuilt
+        compact().unwrap();
+        assert_eq!(
+            autocomplete(String::from("layo"), 10).unwrap(),
+            vec![Completion {
+                text: String::from("layovers"),
+                thread_id: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn facet_counts() {
+        setup();
+
+        // facet counts of every matching document
+        let count = |value: &str, count: u32| facets::FacetCount {
+            value: String::from(value),
+            count,
+        };
+        let with_facets = SearchOptions {
+            limit: 1,
+            facets: true,
+            ..standard()
+        };
+        let response = search(text("deploy"), None, with_facets).unwrap();
+        assert_eq!(response.results.len(), 1);
+        assert_eq!(
+            response.facets,
+            Some(Facets {
+                doc_types: vec![count("card", 3)],
+                threads: vec![count("12", 2), count("9", 1)],
+                tags: vec![],
+            })
+        );
+        assert_eq!(
+            search(text("kubernetes"), None, with_facets)
+                .unwrap()
+                .facets,
+            Some(Facets {
+                doc_types: vec![count("card", 1), count("thread", 1)],
+                threads: vec![],
+                tags: vec![count("infra", 1)],
+            })
+        );
+        assert_eq!(
+            search(text("roadmap"), None, standard()).unwrap().facets,
+            None
+        );
+    }
+
+    #[test]
+    fn grouping() {
+        setup();
+
+        // hits grouped by thread
+        let grouped = SearchOptions {
+            hits_per_thread: Some(1),
+            ..standard()
+        };
+        let response = search(text("roadmap"), None, grouped).unwrap();
+        assert!(response.results.is_empty());
+        let groups = response
+            .groups
+            .unwrap()
+            .into_iter()
+            .map(|group| (group.thread_id, group.hit_count, group.hits.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![
+                (Some(String::from("11")), 2, 1),
+                (Some(String::from("12")), 1, 1),
+                (Some(String::from("9")), 1, 1),
+            ]
+        );
+        let response = search(text("kubernetes"), None, grouped).unwrap();
+        assert_eq!(response.groups.unwrap()[0].thread_id, None);
+    }
+
+    #[test]
+    fn related_cards() {
+        setup();
+        let related_ids = |id: &str, exclude_thread: bool| {
+            let mut found = related(String::from(id), 10, exclude_thread)
+                .unwrap()
+                .into_iter()
+                .map(|result| result.id)
+                .collect::<Vec<String>>();
+            found.sort();
+            found
+        };
+        assert_eq!(related_ids("13", false), vec!["14", "15"]);
+        assert_eq!(related_ids("13", true), vec!["15"]);
+        assert_eq!(related_ids("16", false), Vec::<String>::new());
+        assert!(related(String::from("unknown"), 10, false).is_err());
+        assert!(related(String::from("13"), 0, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn suggestions() {
+        setup();
+        assert_eq!(
+            suggest(String::from("conection brûlée"), standard()).unwrap(),
+            Some(String::from("connection brûlée"))
+        );
+        assert_eq!(
+            suggest(String::from("kubernetes"), standard()).unwrap(),
+            None
+        );
+        assert_eq!(suggest(String::from("xyzzy"), standard()).unwrap(), None);
+    }
+
+    #[test]
+    fn proximity() {
+        setup();
+
+        // proximity between words and between CJK runs
+        let near = |query: &str| ids(search(text(query), None, standard()).unwrap());
+        assert_eq!(near("deploy NEAR/1 failure"), vec!["14"]);
+        assert_eq!(near("deploy NEAR/0 failure"), Vec::<String>::new());
+        assert_eq!(near("\"deploy failure\""), Vec::<String>::new());
+        assert_eq!(near("\"deploy failure\"~1"), vec!["14"]);
+        assert_eq!(near("東京 NEAR/0 国際"), vec!["2"]);
+        assert_eq!(near("東京 NEAR/2 空港"), vec!["2"]);
+        assert_eq!(near("東京 NEAR/1 空港"), Vec::<String>::new());
+        assert_eq!(near("\"英語 haneda\""), vec!["2"]);
+    }
+
+    #[test]
+    fn statistics() {
+        let now = setup();
+        let stats = index_stats().unwrap();
+        assert_eq!(
+            stats.doc_types,
+            vec![
+                facets::FacetCount {
+                    value: String::from("card"),
+                    count: 12
+                },
+                facets::FacetCount {
+                    value: String::from("thread"),
+                    count: 9
+                },
+            ]
+        );
+        assert!(stats.segments >= 1);
+        assert!(stats.size_in_bytes.is_none());
+        assert!(stats.last_commit_at.is_some_and(|millis| millis >= now));
+    }
+
+    #[test]
+    fn compaction() {
+        setup();
+        let _writes = WRITES.lock().unwrap();
+
+        // compaction purges the documents deleted by updates
+        let report = compact().unwrap();
+        assert!(report.reclaimed_bytes.is_none());
+        let stats = index_stats().unwrap();
+        assert_eq!(stats.deleted_docs, 0);
+        assert_eq!(stats.segments, 1);
+    }
+
+    #[test]
+    fn locked_writes() {
+        setup();
+        let _writes = WRITES.lock().unwrap();
+        let zeppelin = || {
+            READER.get().unwrap().reload().unwrap();
+            ids(search(text("zeppelin"), None, standard()).unwrap())
+        };
+
+        // another process holds the writer lock, e.g. a command line tool
+        *WRITER.get().unwrap().lock().unwrap() = None;
+        let other_writer = locking::open_writer(INDEX.get().unwrap()).unwrap();
+        index(vec![target("16", "card", "garden tomatoes zeppelin")]).unwrap();
+        assert_eq!(zeppelin(), Vec::<String>::new());
+
+        // queued writes are applied once the lock is released
+        drop(other_writer);
+        apply_pending_writes().unwrap();
+        assert_eq!(zeppelin(), vec!["16"]);
+    }
+
+    #[test]
+    fn analyzers() {
+        setup();
+        let fixtures = vec![
+            (
+                "Kubernetes 東京",
+                AnalyzerMode::Index,
+                vec![
+                    ("kubernet", 0, 10, 0),
+                    ("東京", 11, 17, 1),
+                    ("京", 14, 17, 2),
+                ],
+            ),
+            (
+                "Kubernetes 東京",
+                AnalyzerMode::Query,
+                vec![("kubernet", 0, 10, 0), ("東京", 11, 17, 1)],
+            ),
+            ("で草", AnalyzerMode::Query, vec![("で草", 0, 6, 0)]),
+            ("!?", AnalyzerMode::Index, vec![]),
+        ];
+        for (input, mode, expected) in fixtures {
+            let expected = expected
+                .into_iter()
+                .map(|(text, offset_from, offset_to, position)| AnalyzedToken {
+                    text: String::from(text),
+                    offset_from,
+                    offset_to,
+                    position,
+                })
+                .collect::<Vec<AnalyzedToken>>();
+            assert_eq!(analyze(String::from(input), mode).unwrap().tokens, expected);
+        }
+        assert_eq!(
+            analyze(String::from("brûlée"), AnalyzerMode::Index)
+                .unwrap()
+                .normalized,
+            "brulee"
+        );
+    }
+
+    #[test]
+    fn explanation() {
+        setup();
+        let explanation = explain(text("deploy failure"), String::from("14"), standard()).unwrap();
+        assert_eq!(
+            explanation.tree,
+            QueryNode::Bool {
+                must: vec![
+                    QueryNode::Term {
+                        text: String::from("deploy"),
+                        field: None,
+                    },
+                    QueryNode::Term {
+                        text: String::from("failure"),
+                        field: None,
+                    },
+                ],
+                should: vec![],
+                must_not: vec![],
+                filter: vec![],
+            }
+        );
+        assert_eq!(explanation.tokens, vec!["deploy", "failur"]);
+        assert!(explanation.score.is_some_and(|score| score > 0.0));
+        assert!(explanation.explanation.is_some());
+        let explanation = explain(text("deploy failure"), String::from("13"), standard()).unwrap();
+        assert!(explanation.score.is_none());
+        assert!(explanation.explanation.is_none());
+        assert!(explain(text("deploy"), String::from("unknown"), standard()).is_err());
+    }
+
+    // The synonyms and the stop words are given to the query builder rather
+    // than set globally, which would change the searches of the other tests.
+    #[test]
+    fn query_settings() {
+        setup();
+        let searcher = READER.get().unwrap().searcher();
+        let search = |query_builder: &QueryBuilder, query: &str| -> Vec<String> {
+            let query = query_builder.build(&text(query)).unwrap().unwrap();
+            let mut ids = searcher
+                .search(&query, &TopDocs::with_limit(100))
+                .unwrap()
+                .into_iter()
+                .map(|(_, doc_address)| to_result(&searcher, doc_address).unwrap().id)
+                .collect::<Vec<String>>();
+            ids.sort();
+            ids
+        };
+        let mut query_builder = query_builder(
+            &searcher,
+            SearchMode::Standard,
+            Fuzziness::Fixed { distance: 0 },
+        )
+        .unwrap();
+        query_builder.synonyms = vec![];
+        query_builder.stop_words = StopWords::new(vec![]);
+        assert!(search(&query_builder, "k8s").is_empty());
+        assert!(search(&query_builder, "the airport").is_empty());
+
+        query_builder.synonyms = vec![vec![String::from("k8s"), String::from("kubernetes")]];
+        assert_eq!(search(&query_builder, "k8s"), vec!["5", "6"]);
+        assert_eq!(search(&query_builder, "K8S upgrade"), vec!["6"]);
+
+        query_builder.stop_words = StopWords::new(vec![StopWordLanguage::English]);
+        assert_eq!(search(&query_builder, "the airport"), vec!["2"]);
+        let typed = QueryNode::Bool {
+            must: vec![
+                QueryNode::Term {
+                    text: String::from("The"),
+                    field: None,
+                },
+                QueryNode::Term {
+                    text: String::from("airport"),
+                    field: None,
+                },
+            ],
+            should: vec![],
+            must_not: vec![],
+            filter: vec![],
+        };
+        let query = query_builder.build(&typed).unwrap().unwrap();
+        assert_eq!(query.count(&searcher).unwrap(), 1);
+        // quoted and scoped stop words are kept
+        assert!(search(&query_builder, "\"the airport\"").is_empty());
+        assert!(search(&query_builder, "text:the airport").is_empty());
+        // a query made only of stop words is searched as it is
+        assert!(search(&query_builder, "the").is_empty());
+        assert_eq!(
+            query_builder.tokens(&text("the airport")).unwrap(),
+            vec!["airport"]
+        );
+    }
+
+    #[test]
+    fn autocompletion() {
+        setup();
+        assert_eq!(
+            autocomplete(String::from("kube"), 10).unwrap(),
+            vec![
+                Completion {
+                    text: String::from("Kubernetes"),
+                    thread_id: Some(String::from("5")),
+                },
+                Completion {
+                    text: String::from("kubernetes"),
+                    thread_id: None,
+                },
+            ]
+        );
+        assert_eq!(
+            autocomplete(String::from("東京国"), 10).unwrap(),
+            vec![Completion {
+                text: String::from("東京国際"),
+                thread_id: None,
+            }]
+        );
+        assert!(autocomplete(String::from("kube "), 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn patterns() {
+        setup();
+
+        // regex and wildcard patterns match indexed words
+        let matching = |node: QueryNode| search(node, None, standard()).map(sorted_ids);
+        assert_eq!(
+            matching(QueryNode::Regex {
+                pattern: String::from("MILE[a-z]+"),
+                field: Some(QueryField::Text),
+            })
+            .unwrap(),
+            vec!["7", "8"]
+        );
+        assert_eq!(
+            matching(QueryNode::Wildcard {
+                pattern: String::from("dep?oy*"),
+                field: None,
+            })
+            .unwrap(),
+            vec!["13", "14", "15"]
+        );
+        assert_eq!(
+            matching(QueryNode::Wildcard {
+                pattern: String::from("Deploy-*-fail*"),
+                field: None,
+            })
+            .unwrap(),
+            vec!["14"]
+        );
+        assert!(matching(QueryNode::Wildcard {
+            pattern: String::from("pipeline-deploy"),
+            field: None,
+        })
+        .unwrap()
+        .is_empty());
+        assert!(matching(QueryNode::Regex {
+            pattern: String::from("[a-z]{100}"),
+            field: None,
+        })
+        .is_err());
+        assert_eq!(
+            matching(QueryNode::Regex {
+                pattern: String::from("failure"),
+                field: None,
+            })
+            .unwrap(),
+            vec!["14"]
+        );
+        assert_eq!(
+            matching(QueryNode::Regex {
+                pattern: String::from("milestone"),
+                field: None,
+            })
+            .unwrap(),
+            vec!["7", "8"]
+        );
+        assert_eq!(
+            matching(QueryNode::Wildcard {
+                pattern: String::from("*ure"),
+                field: None,
+            })
+            .unwrap(),
+            vec!["14"]
+        );
+        assert_eq!(
+            matching(QueryNode::Wildcard {
+                pattern: String::from("deploy-*-fail*"),
+                field: Some(QueryField::Text),
+            })
+            .unwrap(),
+            vec!["14"]
+        );
+        assert!(matching(QueryNode::Wildcard {
+            pattern: String::from("deploy-*-fail*"),
+            field: Some(QueryField::Title),
+        })
+        .unwrap()
+        .is_empty());
+
+        // characters at which words are split make a phrase
+        assert_eq!(
+            matching(QueryNode::Regex {
+                pattern: String::from(r"TODO\([a-z]+\)"),
+                field: None,
+            })
+            .unwrap(),
+            vec!["17"]
+        );
+        assert_eq!(
+            matching(QueryNode::Regex {
+                pattern: String::from(r"deploy-[a-z]+"),
+                field: None,
+            })
+            .unwrap(),
+            vec!["13", "14", "15"]
+        );
+        // a part matching too many words to search every phrase only needs
+        // to match a word of the document
+        assert_eq!(
+            matching(QueryNode::Regex {
+                pattern: String::from("roadmap-.+"),
+                field: None,
+            })
+            .unwrap(),
+            vec!["10", "11", "12", "9"]
+        );
+        assert!(matching(QueryNode::Regex {
+            pattern: String::from(r"(deploy-[a-z]+)"),
+            field: None,
+        })
+        .is_err());
     }
 }
@@ -0,0 +1,89 @@
+use super::normalize;
+use cjk::is_cjk_codepoint;
+use std::collections::HashMap;
+use tantivy::schema::Field;
+use tantivy::Searcher;
+
+/// Splits the last word being typed into the part kept as it is and the
+/// part completed from the term dictionary. Latin words are indexed as
+/// whole words, so the trailing run of Latin characters is completed.
+/// CJK text is indexed as bigrams, so only its last character is completed
+/// with the bigrams starting with it, suggesting the next character.
+fn split_word(word: &str) -> (&str, &str) {
+    let Some((index, last)) = word.char_indices().last() else {
+        return (word, "");
+    };
+    if is_cjk_codepoint(last) {
+        return word.split_at(index);
+    }
+
+    let start = word
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() && !is_cjk_codepoint(*c))
+        .last()
+        .map_or(word.len(), |(index, _)| index);
+    word.split_at(start)
+}
+
+/// Returns the texts completing the last word of the prefix with the most
+/// frequent words of the dictionary field. Completed words are lowercased.
+pub fn complete_terms(
+    searcher: &Searcher,
+    field: Field,
+    prefix: &str,
+    limit: usize,
+) -> anyhow::Result<Vec<String>> {
+    let Some(word) = prefix.split_whitespace().last() else {
+        return Ok(vec![]);
+    };
+    // The last word is complete once it is followed by a space.
+    if !prefix.ends_with(word) {
+        return Ok(vec![]);
+    }
+    let head = &prefix[..prefix.len() - word.len()];
+    let word = normalize(word).to_lowercase();
+    let (kept, partial) = split_word(&word);
+    if partial.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut doc_freqs: HashMap<String, u64> = HashMap::new();
+    for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader.inverted_index(field)?;
+        let mut stream = inverted_index.terms().range().ge(partial).into_stream()?;
+        while stream.advance() {
+            if !stream.key().starts_with(partial.as_bytes()) {
+                break;
+            }
+            let term = String::from_utf8_lossy(stream.key()).to_string();
+            *doc_freqs.entry(term).or_default() += stream.value().doc_freq as u64;
+        }
+    }
+
+    let mut terms = doc_freqs
+        .into_iter()
+        .filter(|(term, _)| term != partial)
+        .collect::<Vec<(String, u64)>>();
+    terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    Ok(terms
+        .into_iter()
+        .take(limit)
+        .map(|(term, _)| format!("{}{}{}", head, kept, term))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        assert_eq!(split_word("kube"), ("", "kube"));
+        assert_eq!(split_word("羽田air"), ("羽田", "air"));
+        assert_eq!(split_word("東京国"), ("東京", "国"));
+        assert_eq!(split_word("v1.2"), ("v1.", "2"));
+        assert_eq!(split_word("c++"), ("c++", ""));
+    }
+}
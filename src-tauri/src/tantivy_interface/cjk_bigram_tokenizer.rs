@@ -45,7 +45,9 @@ pub struct CJKBigramTokenStream<'a> {
 impl<'a> TokenStream for CJKBigramTokenStream<'a> {
     fn advance(&mut self) -> bool {
         if let Some((offset_from, offset_to)) = self.iterator.next() {
-            self.token.position = 0;
+            // Every bigram takes a position, so that a CJK run occupies
+            // one position per character and phrases of bigrams line up.
+            self.token.position = self.token.position.wrapping_add(1);
             self.token.offset_from = offset_from;
             self.token.offset_to = offset_to;
             self.token.text.clear();
@@ -205,6 +207,21 @@ mod test {
         assert_eq!("mafia", &text[from..to]);
         assert!((&mut iterator).next().is_none());
 
+        let mut tokenizer = CJKBigramTokenizer::new();
+        let mut token_stream = tokenizer.token_stream("東京 Airport");
+        let mut positions = vec![];
+        token_stream.process(&mut |token| {
+            positions.push((token.text.clone(), token.position));
+        });
+        assert_eq!(
+            positions,
+            vec![
+                (String::from("東京"), 0),
+                (String::from("京"), 1),
+                (String::from("Airport"), 2)
+            ]
+        );
+
         let text = "で草";
         let mut iterator = CJKBigramIterator::new(text, true);
         let (from, to) = (&mut iterator).next().unwrap();
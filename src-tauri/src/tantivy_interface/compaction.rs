@@ -0,0 +1,117 @@
+use super::locking::WriterGuard;
+use super::ranking::now_in_millis;
+use super::stats::directory_size;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::thread;
+use std::time::Duration;
+use tantivy::{Index, IndexReader, Searcher};
+
+// Event emitted with a `CompactionReport` when the background job
+// has compacted the index.
+pub const COMPACTED_EVENT: &str = "index-compacted";
+
+const IDLE_DELAY_IN_MILLIS: i64 = 5 * 60 * 1000;
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+// The background job leaves the index alone until there is enough to reclaim.
+const MIN_DELETED_RATIO: f32 = 0.1;
+const MAX_SEGMENTS: usize = 8;
+
+static LAST_ACTIVITY: AtomicI64 = AtomicI64::new(0);
+
+/// `reclaimed_bytes` is `None` for an index in memory.
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CompactionReport {
+    pub merged_segments: u32,
+    pub purged_docs: u32,
+    pub deleted_files: u32,
+    pub reclaimed_bytes: Option<f64>,
+}
+
+/// Postpones the background job, as the index is in use.
+pub fn touch() {
+    LAST_ACTIVITY.store(now_in_millis(), Ordering::Relaxed);
+}
+
+fn is_idle() -> bool {
+    now_in_millis() - LAST_ACTIVITY.load(Ordering::Relaxed) >= IDLE_DELAY_IN_MILLIS
+}
+
+pub fn needs_compaction(searcher: &Searcher) -> bool {
+    let segment_readers = searcher.segment_readers();
+    let deleted_docs = segment_readers
+        .iter()
+        .map(|segment_reader| segment_reader.num_deleted_docs())
+        .sum::<u32>();
+    let docs = segment_readers
+        .iter()
+        .map(|segment_reader| segment_reader.max_doc())
+        .sum::<u32>();
+
+    segment_readers.len() > MAX_SEGMENTS
+        || (docs > 0 && deleted_docs as f32 / docs as f32 >= MIN_DELETED_RATIO)
+}
+
+/// Merges every segment into one, which purges the deleted documents,
+/// then deletes the files no longer used by the index.
+/// The writer is locked only to start each step, so that documents can be
+/// indexed while the segments are merged.
+pub fn compact(
+    index: &Index,
+    index_path: &Option<PathBuf>,
+    reader: &IndexReader,
+    lock_writer: impl Fn() -> anyhow::Result<WriterGuard>,
+) -> anyhow::Result<CompactionReport> {
+    let size = || -> anyhow::Result<Option<u64>> {
+        index_path
+            .as_ref()
+            .map(|path| directory_size(path))
+            .transpose()
+    };
+    let size_before = size()?;
+
+    reader.reload()?;
+    let purged_docs = reader
+        .searcher()
+        .segment_readers()
+        .iter()
+        .map(|segment_reader| segment_reader.num_deleted_docs())
+        .sum::<u32>();
+    let segment_ids = index.searchable_segment_ids()?;
+    let merged_segments = if segment_ids.len() > 1 || purged_docs > 0 {
+        let merge = lock_writer()?.merge(&segment_ids);
+        merge.wait()?;
+        segment_ids.len()
+    } else {
+        0
+    };
+    let garbage_collection = lock_writer()?.garbage_collect_files();
+    let garbage_collection = garbage_collection.wait()?;
+    reader.reload()?;
+
+    Ok(CompactionReport {
+        merged_segments: merged_segments as u32,
+        purged_docs,
+        deleted_files: garbage_collection.deleted_files.len() as u32,
+        reclaimed_bytes: size_before
+            .zip(size()?)
+            .map(|(before, after)| before.saturating_sub(after) as f64),
+    })
+}
+
+/// Runs the job on a background thread whenever the index has not been
+/// used for a while.
+pub fn spawn_idle_job<F: FnMut() + Send + 'static>(mut job: F) {
+    touch();
+    thread::spawn(move || loop {
+        thread::sleep(CHECK_INTERVAL);
+        if is_idle() {
+            job();
+            touch();
+        }
+    });
+}
@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use tantivy::collector::{FacetCollector, FacetCounts};
+use tantivy::schema::Facet;
+
+// Facet field holding "/type/<doc_type>" and "/tag/<tag>" of each document.
+// Thread counts come from the thread path facet.
+pub const CATEGORIES: &str = "categories";
+const DOC_TYPE: &str = "type";
+const TAG: &str = "tag";
+
+#[cfg_attr(debug_assertions, derive(Type, Debug, PartialEq))]
+#[derive(Serialize, Deserialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: u32,
+}
+
+/// Number of matching documents per value, most frequent first.
+/// `threads` counts documents per top-level thread.
+#[cfg_attr(debug_assertions, derive(Type, Debug, PartialEq))]
+#[derive(Serialize, Deserialize)]
+pub struct Facets {
+    pub doc_types: Vec<FacetCount>,
+    pub threads: Vec<FacetCount>,
+    pub tags: Vec<FacetCount>,
+}
+
+pub fn doc_type(doc_type: &str) -> Facet {
+    Facet::from_path([DOC_TYPE, doc_type])
+}
+
+pub fn tag(tag: &str) -> Facet {
+    Facet::from_path([TAG, tag])
+}
+
+pub fn collector(thread_path_field: &str) -> (FacetCollector, FacetCollector) {
+    let mut threads = FacetCollector::for_field(thread_path_field);
+    threads.add_facet(Facet::root());
+
+    let mut categories = FacetCollector::for_field(CATEGORIES);
+    categories.add_facet(Facet::from_path([DOC_TYPE]));
+    categories.add_facet(Facet::from_path([TAG]));
+
+    (threads, categories)
+}
+
+pub fn counts((threads, categories): (FacetCounts, FacetCounts)) -> Facets {
+    Facets {
+        doc_types: children(&categories, Facet::from_path([DOC_TYPE])),
+        threads: children(&threads, Facet::root()),
+        tags: children(&categories, Facet::from_path([TAG])),
+    }
+}
+
+fn children(counts: &FacetCounts, parent: Facet) -> Vec<FacetCount> {
+    let mut children = counts
+        .get(parent)
+        .filter_map(|(facet, count)| {
+            Some(FacetCount {
+                value: facet.to_path().last()?.to_string(),
+                count: count as u32,
+            })
+        })
+        .collect::<Vec<FacetCount>>();
+    children.sort_by_key(|child| std::cmp::Reverse(child.count));
+    children
+}
@@ -0,0 +1,53 @@
+use super::settings;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::sync::RwLock;
+use tauri::AppHandle;
+
+const SETTINGS_KEY: &str = "field_boosts";
+
+/// Weight of a match in each field when a query is not scoped to a field.
+#[cfg_attr(debug_assertions, derive(Type, Debug, PartialEq))]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct FieldBoosts {
+    pub title: f32,
+    pub text: f32,
+    pub tags: f32,
+}
+
+const DEFAULT_BOOSTS: FieldBoosts = FieldBoosts {
+    title: 3.0,
+    text: 1.0,
+    tags: 2.0,
+};
+
+static BOOSTS: RwLock<FieldBoosts> = RwLock::new(DEFAULT_BOOSTS);
+
+pub fn load(app_handle: &AppHandle) -> anyhow::Result<()> {
+    let boosts = settings::read::<FieldBoosts>(app_handle, SETTINGS_KEY)?;
+    set(boosts.unwrap_or(DEFAULT_BOOSTS))
+}
+
+pub fn save(app_handle: &AppHandle, boosts: FieldBoosts) -> anyhow::Result<()> {
+    settings::write(app_handle, SETTINGS_KEY, &boosts)?;
+    set(boosts)
+}
+
+pub fn get() -> anyhow::Result<FieldBoosts> {
+    let boosts = BOOSTS.read().map_err(|e| anyhow!(e.to_string()))?;
+    Ok(*boosts)
+}
+
+pub fn set(boosts: FieldBoosts) -> anyhow::Result<()> {
+    if [boosts.title, boosts.text, boosts.tags]
+        .iter()
+        .any(|boost| !boost.is_finite() || *boost < 0.0)
+    {
+        return Err(anyhow!("Field boosts must be non-negative numbers"));
+    }
+
+    let mut current = BOOSTS.write().map_err(|e| anyhow!(e.to_string()))?;
+    *current = boosts;
+    Ok(())
+}
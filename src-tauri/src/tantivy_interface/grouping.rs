@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::fastfield::FacetReader;
+use tantivy::schema::Facet;
+use tantivy::{DocAddress, DocId, Score, SegmentOrdinal, SegmentReader};
+
+/// Hits of a thread. The score of the thread is the sum of the scores
+/// of all its hits, so both the relevance and the number of hits count.
+#[derive(Default)]
+pub struct Group {
+    pub hit_count: u32,
+    pub score: Score,
+    // best hits first
+    pub hits: Vec<(Score, DocAddress)>,
+}
+
+impl Group {
+    fn add(&mut self, score: Score, address: DocAddress, hits_per_thread: usize) {
+        self.hit_count += 1;
+        self.score += score;
+        self.hits.push((score, address));
+        self.keep_best(hits_per_thread);
+    }
+
+    fn merge(&mut self, other: Group, hits_per_thread: usize) {
+        self.hit_count += other.hit_count;
+        self.score += other.score;
+        self.hits.extend(other.hits);
+        self.keep_best(hits_per_thread);
+    }
+
+    fn keep_best(&mut self, hits_per_thread: usize) {
+        self.hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+        self.hits.truncate(hits_per_thread);
+    }
+}
+
+/// Collects every hit into the group of the thread containing it, which is
+/// the last id of its thread path. A thread is grouped with its own cards.
+/// Documents without a thread path are grouped under `None`.
+pub struct ThreadGroupCollector {
+    pub thread_path_field: String,
+    pub hits_per_thread: usize,
+}
+
+pub struct ThreadGroupSegmentCollector {
+    segment_ord: SegmentOrdinal,
+    facet_reader: FacetReader,
+    hits_per_thread: usize,
+    groups: HashMap<Option<u64>, Group>,
+}
+
+impl Collector for ThreadGroupCollector {
+    type Fruit = HashMap<Option<String>, Group>;
+    type Child = ThreadGroupSegmentCollector;
+
+    fn for_segment(
+        &self,
+        segment_local_id: SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        Ok(ThreadGroupSegmentCollector {
+            segment_ord: segment_local_id,
+            facet_reader: segment.facet_reader(&self.thread_path_field)?,
+            hits_per_thread: self.hits_per_thread,
+            groups: HashMap::new(),
+        })
+    }
+
+    fn requires_scoring(&self) -> bool {
+        true
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<HashMap<Option<String>, Group>>,
+    ) -> tantivy::Result<Self::Fruit> {
+        let mut groups: HashMap<Option<String>, Group> = HashMap::new();
+        for fruit in segment_fruits {
+            for (thread_id, group) in fruit {
+                groups
+                    .entry(thread_id)
+                    .or_default()
+                    .merge(group, self.hits_per_thread);
+            }
+        }
+        Ok(groups)
+    }
+}
+
+impl SegmentCollector for ThreadGroupSegmentCollector {
+    type Fruit = HashMap<Option<String>, Group>;
+
+    fn collect(&mut self, doc: DocId, score: Score) {
+        let facet_ord = self.facet_reader.facet_ords(doc).next();
+        self.groups.entry(facet_ord).or_default().add(
+            score,
+            DocAddress::new(self.segment_ord, doc),
+            self.hits_per_thread,
+        );
+    }
+
+    fn harvest(self) -> Self::Fruit {
+        let mut groups: HashMap<Option<String>, Group> = HashMap::new();
+        for (facet_ord, group) in self.groups {
+            let thread_id = facet_ord.and_then(|ord| {
+                let mut facet = Facet::root();
+                self.facet_reader.facet_from_ord(ord, &mut facet).ok()?;
+                facet.to_path().last().map(|id| id.to_string())
+            });
+            groups
+                .entry(thread_id)
+                .or_default()
+                .merge(group, self.hits_per_thread);
+        }
+        groups
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::anyhow;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+use tantivy::directory::error::LockError;
+use tantivy::{Index, IndexWriter, TantivyError};
+
+// A second instance of the app is forwarded to the running one, so the
+// writer lock of the index can only be held by another program, e.g. a
+// command line tool. The index is then searched as committed by that
+// program, and writes are queued until the lock is released.
+
+const MEMORY_BUDGET_IN_BYTES: usize = 100_000_000;
+
+pub const INDEX_LOCKED: &str = "The index is locked by another process";
+
+#[derive(Debug)]
+pub struct IndexLockedError;
+
+impl fmt::Display for IndexLockedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", INDEX_LOCKED)
+    }
+}
+
+impl std::error::Error for IndexLockedError {}
+
+pub fn open_writer(index: &Index) -> anyhow::Result<IndexWriter> {
+    match index.writer(MEMORY_BUDGET_IN_BYTES) {
+        Err(TantivyError::LockFailure(LockError::LockBusy, _)) => Err(IndexLockedError.into()),
+        result => Ok(result?),
+    }
+}
+
+/// Writer of the index, opened by `lock` if it was not yet.
+pub struct WriterGuard(MutexGuard<'static, Option<IndexWriter>>);
+
+impl Deref for WriterGuard {
+    type Target = IndexWriter;
+
+    fn deref(&self) -> &IndexWriter {
+        self.0.as_ref().expect("the writer is opened by `lock`")
+    }
+}
+
+impl DerefMut for WriterGuard {
+    fn deref_mut(&mut self) -> &mut IndexWriter {
+        self.0.as_mut().expect("the writer is opened by `lock`")
+    }
+}
+
+/// Locks the writer, retrying to open it when another process held
+/// the lock of the index so far.
+pub fn lock(
+    writer: &'static Mutex<Option<IndexWriter>>,
+    index: &Index,
+) -> anyhow::Result<WriterGuard> {
+    let mut guard = writer.lock().map_err(|e| anyhow!(e.to_string()))?;
+    if guard.is_none() {
+        *guard = Some(open_writer(index)?);
+    }
+    Ok(WriterGuard(guard))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tantivy::schema::Schema;
+
+    #[test]
+    fn test() {
+        let index = Index::create_in_ram(Schema::builder().build());
+        static WRITER: Mutex<Option<IndexWriter>> = Mutex::new(None);
+
+        // the lock is held by another writer, e.g. of another process
+        let other_writer = open_writer(&index).unwrap();
+        let error = lock(&WRITER, &index).err().unwrap();
+        assert!(error.is::<IndexLockedError>());
+        assert_eq!(error.to_string(), INDEX_LOCKED);
+
+        // released
+        drop(other_writer);
+        assert!(lock(&WRITER, &index).is_ok());
+        assert!(WRITER.lock().unwrap().is_some());
+    }
+}
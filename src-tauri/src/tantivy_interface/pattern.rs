@@ -0,0 +1,241 @@
+use super::normalize;
+use anyhow::anyhow;
+use tantivy::schema::Field;
+use tantivy::Searcher;
+use tantivy_fst::Regex;
+
+// Patterns are matched against every term of the dictionary, and the size
+// of their automaton grows with their length and their repetitions.
+// Tantivy also rejects automata with too many states, but only after
+// spending time on building them, so oversized patterns are rejected first.
+const MAX_PATTERN_LENGTH: usize = 100;
+// Applies to the product of the counts of `{n}`, `{n,}` and `{n,m}`,
+// since nested repetitions multiply the size of the automaton.
+const MAX_REPETITION: u64 = 64;
+// A pattern spanning several words is searched as one phrase per
+// combination of the indexed words matching its parts.
+pub const MAX_PHRASES: usize = 64;
+
+/// Compiles a regular expression matched against whole indexed words.
+/// Indexed words are lowercased, so the pattern is case-insensitive.
+/// Patterns still containing a character at which the text is split into
+/// words, e.g. `(todo-\w+)`, are rejected as they can never match.
+pub fn regex(pattern: &str) -> anyhow::Result<Regex> {
+    if pattern.chars().count() > MAX_PATTERN_LENGTH {
+        return Err(anyhow!(
+            "pattern is longer than {} characters",
+            MAX_PATTERN_LENGTH
+        ));
+    }
+    if repetition(pattern) > MAX_REPETITION {
+        return Err(anyhow!(
+            "pattern repeats more than {} times",
+            MAX_REPETITION
+        ));
+    }
+    if let Some(separator) = separator(pattern) {
+        return Err(anyhow!(
+            "pattern contains '{}', at which words are split",
+            separator
+        ));
+    }
+
+    Regex::new(&format!("(?i){}", pattern)).map_err(|e| anyhow!("invalid pattern: {}", e))
+}
+
+/// Splits a regular expression into the regular expressions of the words
+/// it spans, at the characters matched literally at which the text is split
+/// into words, e.g. `todo\(\w+\)` gives `todo` and `\w+`. A quantifier of
+/// such a character goes with it. Characters inside groups and alternations
+/// of the whole pattern are left to `regex`, which rejects them.
+pub fn split(pattern: &str) -> Vec<String> {
+    let mut regexes = vec![];
+    let mut regex = String::new();
+    let mut depth = 0;
+    let mut in_class = false;
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        let is_top_level = depth == 0 && !in_class;
+        let is_separator = match c {
+            '\\' => {
+                let escaped = chars.next();
+                let is_separator = escaped
+                    .is_some_and(|escaped| !escaped.is_alphanumeric() || "sW".contains(escaped));
+                if !is_top_level || !is_separator {
+                    regex.push(c);
+                    regex.extend(escaped);
+                }
+                is_top_level && is_separator
+            }
+            '|' if is_top_level => return vec![String::from(pattern)],
+            c if is_top_level && !c.is_alphanumeric() && !is_meta_character(c) => true,
+            c => {
+                match c {
+                    '[' => in_class = true,
+                    ']' => in_class = false,
+                    '(' if !in_class => depth += 1,
+                    ')' if !in_class && depth > 0 => depth -= 1,
+                    _ => {}
+                }
+                regex.push(c);
+                false
+            }
+        };
+        if is_separator {
+            while let Some(quantifier) = chars.next_if(|c| "?*+{".contains(*c)) {
+                if quantifier == '{' {
+                    chars.by_ref().take_while(|c| *c != '}').for_each(drop);
+                }
+            }
+            if !regex.is_empty() {
+                regexes.push(std::mem::take(&mut regex));
+            }
+        }
+    }
+    if !regex.is_empty() {
+        regexes.push(regex);
+    }
+    regexes
+}
+
+/// Converts a wildcard pattern to the regular expressions of the words it
+/// spans, where `*` matches any characters and `?` a single character.
+/// The pattern is split into words like the indexed text, at characters
+/// which are neither letters nor digits and between letters and digits,
+/// e.g. `Proj-*-2024` gives `proj`, `.*` and `2024`. A lone `*` then
+/// stands for a single word.
+/// The rest of the pattern is normalized like the indexed words.
+pub fn wildcard(pattern: &str) -> Vec<String> {
+    let mut regexes = vec![];
+    let mut regex = String::new();
+    let mut previous: Option<char> = None;
+    for c in normalize(pattern).to_lowercase().chars() {
+        let is_boundary = match c {
+            '*' | '?' => false,
+            c if !c.is_alphanumeric() => true,
+            c => previous.is_some_and(|previous| previous.is_numeric() != c.is_numeric()),
+        };
+        if is_boundary && !regex.is_empty() {
+            regexes.push(std::mem::take(&mut regex));
+        }
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c if c.is_alphanumeric() => {
+                regex.push(c);
+                previous = Some(c);
+            }
+            _ => previous = None,
+        }
+    }
+    if !regex.is_empty() {
+        regexes.push(regex);
+    }
+    regexes
+}
+
+/// Indexed words of the field matching the regular expression, or `None`
+/// when more than `MAX_PHRASES` of them do.
+pub fn expand(
+    searcher: &Searcher,
+    field: Field,
+    regex: &Regex,
+) -> anyhow::Result<Option<Vec<String>>> {
+    let mut words = vec![];
+    for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader.inverted_index(field)?;
+        let mut stream = inverted_index.terms().search(regex).into_stream()?;
+        while stream.advance() {
+            let word = String::from_utf8_lossy(stream.key()).to_string();
+            if !words.contains(&word) {
+                if words.len() == MAX_PHRASES {
+                    return Ok(None);
+                }
+                words.push(word);
+            }
+        }
+    }
+    Ok(Some(words))
+}
+
+fn is_meta_character(c: char) -> bool {
+    r"\.+*?()|[]{}^$".contains(c)
+}
+
+// First character of the pattern matched literally which is neither
+// a letter nor a digit. Character classes are not checked.
+fn separator(pattern: &str) -> Option<char> {
+    let mut in_class = false;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) if !in_class && !escaped.is_alphanumeric() => return Some(escaped),
+                _ => {}
+            },
+            '[' => in_class = true,
+            ']' => in_class = false,
+            c if in_class || c.is_alphanumeric() || is_meta_character(c) => {}
+            c => return Some(c),
+        }
+    }
+    None
+}
+
+// Product of the counts of the counted repetitions of the pattern.
+// Escaped braces are literal characters.
+fn repetition(pattern: &str) -> u64 {
+    let mut product: u64 = 1;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '{' => {
+                let counts = chars.by_ref().take_while(|c| *c != '}').collect::<String>();
+                let count = counts
+                    .split(',')
+                    .filter_map(|count| count.trim().parse::<u64>().ok())
+                    .max()
+                    .unwrap_or(1);
+                product = product.saturating_mul(count.max(1));
+            }
+            _ => {}
+        }
+    }
+    product
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        assert_eq!(wildcard("Proj-*-2024"), vec!["proj", ".*", "2024"]);
+        assert_eq!(wildcard("brûl?e"), vec!["brul.e"]);
+        assert_eq!(wildcard("route66*"), vec!["route", "66.*"]);
+        assert!(wildcard("--").is_empty());
+
+        assert_eq!(repetition("a{2}b{3,5}"), 10);
+        assert_eq!(repetition("a\\{100}"), 1);
+        assert_eq!(repetition("(a{10}){10,}"), 100);
+
+        assert!(regex("todo[0-9]+").is_ok());
+        assert!(regex("todo(").is_err());
+        assert!(regex(r"todo\(\w+\)").is_err());
+        assert!(regex(r"(todo-\w+)").is_err());
+
+        assert_eq!(split(r"TODO\(\w+\)"), vec!["TODO", r"\w+"]);
+        assert_eq!(split(r"deploy\s+pipe[a-z]*"), vec!["deploy", "pipe[a-z]*"]);
+        assert_eq!(split("proj-?2024"), vec!["proj", "2024"]);
+        assert_eq!(split(r"todo\w+[-_]?"), vec![r"todo\w+[-_]?"]);
+        assert_eq!(split("(todo-done)"), vec!["(todo-done)"]);
+        assert_eq!(split("todo|done-item"), vec!["todo|done-item"]);
+        assert!(regex("proj-2024").is_err());
+        assert!(regex(r"todo\w+[-_]?").is_ok());
+        assert!(regex("(a{10}){10}").is_err());
+        assert!(regex(&"a".repeat(MAX_PATTERN_LENGTH + 1)).is_err());
+    }
+}
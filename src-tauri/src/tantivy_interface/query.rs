@@ -0,0 +1,1238 @@
+use super::field_boosts::FieldBoosts;
+use super::ranking::{CREATED_AT, UPDATED_AT};
+use super::stop_words::StopWords;
+use super::{normalize, pattern, synonyms, threads};
+use cjk::is_cjk_codepoint;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::iter::Peekable;
+use std::ops::Bound;
+use std::str::Chars;
+use std::sync::Arc;
+use tantivy::query::{
+    AllQuery, BooleanQuery, BoostQuery, ConstScoreQuery, EmptyQuery, FuzzyTermQuery, Occur,
+    PhraseQuery, Query, RangeQuery, RegexQuery, TermQuery,
+};
+use tantivy::schema::{Field, IndexRecordOption, Term};
+use tantivy::tokenizer::{TextAnalyzer, TokenStream};
+use tantivy::{DateTime, Searcher};
+
+/// Query tree accepted by `search`.
+/// `Text` holds raw input of the search box and is parsed leniently,
+/// so it can be mixed with nodes built programmatically by the frontend.
+#[cfg_attr(debug_assertions, derive(Type, Debug, PartialEq))]
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "kind")]
+pub enum QueryNode {
+    Text {
+        text: String,
+    },
+    Term {
+        text: String,
+        field: Option<QueryField>,
+    },
+    // `slop` is the number of extra positions allowed between the words,
+    // as for `PhraseQuery::set_slop`.
+    Phrase {
+        text: String,
+        field: Option<QueryField>,
+        #[serde(default)]
+        slop: u32,
+    },
+    // Each text is searched as a phrase, and the texts must appear within
+    // `distance` extra positions of each other, counted as the slop of
+    // a phrase. A CJK run takes one position per character.
+    Near {
+        texts: Vec<String>,
+        field: Option<QueryField>,
+        distance: u32,
+    },
+    // Word being typed; only its last token is prefix-expanded.
+    Prefix {
+        text: String,
+        field: Option<QueryField>,
+    },
+    // Matches whole words as they were written, lowercased. Characters at
+    // which the text is split into words, e.g. in `todo\(\w+\)`, split the
+    // pattern into words matched as a phrase.
+    Regex {
+        pattern: String,
+        field: Option<QueryField>,
+    },
+    // `*` matches any characters and `?` a single character.
+    // A pattern spanning several words, e.g. `proj-*-2024`, matches them
+    // as a phrase, where a lone `*` stands for any single word.
+    Wildcard {
+        pattern: String,
+        field: Option<QueryField>,
+    },
+    DocType {
+        doc_type: String,
+    },
+    // The thread and its descendants.
+    Thread {
+        thread_id: String,
+    },
+    // Unix times in milliseconds. `from` is inclusive and `to` is exclusive,
+    // e.g. the last 7 days is `{ from: Date.now() - 7 * 86400000 }`.
+    DateRange {
+        field: DateField,
+        from: Option<f64>,
+        to: Option<f64>,
+    },
+    Bool {
+        must: Vec<QueryNode>,
+        should: Vec<QueryNode>,
+        must_not: Vec<QueryNode>,
+        // Clauses which restrict the results without affecting the score.
+        filter: Vec<QueryNode>,
+    },
+}
+
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum QueryField {
+    Title,
+    Text,
+    Tags,
+}
+
+impl QueryField {
+    fn from_name(name: &str) -> Option<QueryField> {
+        match name {
+            "title" => Some(QueryField::Title),
+            "text" | "body" => Some(QueryField::Text),
+            "tag" | "tags" => Some(QueryField::Tags),
+            _ => None,
+        }
+    }
+}
+
+#[cfg_attr(debug_assertions, derive(Type, Debug, PartialEq))]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum DateField {
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl DateField {
+    fn name(&self) -> &'static str {
+        match self {
+            DateField::CreatedAt => CREATED_AT,
+            DateField::UpdatedAt => UPDATED_AT,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct SearchFields {
+    pub id: Field,
+    pub title: Field,
+    pub text: Field,
+    pub tags: Field,
+    pub doc_type: Field,
+    pub thread_path: Field,
+    pub words: Field,
+}
+
+/// `Standard` prefix-expands every word of the query.
+/// `Incremental` is meant for search-as-you-type: only the word being typed
+/// is prefix-expanded, and the other words must match as complete words.
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum SearchMode {
+    Standard,
+    Incremental,
+}
+
+/// Maximum edit distance allowed between query words and indexed words.
+/// `Auto` picks the distance from the length of each word.
+/// Fuzziness is never applied to CJK tokens: a typo in a bigram is
+/// a different word rather than a misspelling.
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(tag = "kind")]
+pub enum Fuzziness {
+    Auto,
+    Fixed { distance: u8 },
+}
+
+impl Fuzziness {
+    pub fn distance(&self, token: &str) -> u8 {
+        if token.chars().any(is_cjk_codepoint) {
+            return 0;
+        }
+
+        match self {
+            Fuzziness::Fixed { distance } => *distance,
+            Fuzziness::Auto => match token.chars().count() {
+                0..=2 => 0,
+                3..=5 => 1,
+                _ => 2,
+            },
+        }
+    }
+}
+
+// Shorter Latin words are not prefix-expanded, because a prefix like "c"
+// expands to a large part of the dictionary.
+const MIN_PREFIX_LENGTH: usize = 2;
+const COMPLETE_WORD_BOOST: f32 = 2.0;
+
+// Built for each search request and never mutated, so that concurrent
+// searches don't share any state other than the searcher.
+pub struct QueryBuilder {
+    pub searcher: Searcher,
+    pub fields: SearchFields,
+    pub boosts: FieldBoosts,
+    pub synonyms: Vec<Vec<String>>,
+    pub stop_words: StopWords,
+    pub analyzer: TextAnalyzer,
+    pub mode: SearchMode,
+    pub fuzziness: Fuzziness,
+}
+
+impl QueryBuilder {
+    /// Returns `None` when the node has nothing to search for,
+    /// e.g. a word made only of punctuation.
+    pub fn build(&self, node: &QueryNode) -> anyhow::Result<Option<Box<dyn Query>>> {
+        match node {
+            QueryNode::Text { text } => self.build(&self.parse_text(text)?),
+            QueryNode::Term { text, field } => self.build_term(text, field, false),
+            QueryNode::Phrase { text, field, slop } => {
+                Ok(self.build_phrase(&[text.as_str()], field, *slop))
+            }
+            QueryNode::Near {
+                texts,
+                field,
+                distance,
+            } => {
+                let texts = texts.iter().map(String::as_str).collect::<Vec<&str>>();
+                Ok(self.build_phrase(&texts, field, *distance))
+            }
+            QueryNode::Prefix { text, field } => self.build_term(text, field, true),
+            QueryNode::Regex { pattern, field } => self.build_pattern(pattern, field),
+            QueryNode::Wildcard { pattern, field } => self.build_wildcard(pattern, field),
+            QueryNode::DocType { doc_type } => Ok(Some(Box::new(TermQuery::new(
+                Term::from_field_text(self.fields.doc_type, doc_type),
+                IndexRecordOption::Basic,
+            )))),
+            QueryNode::Thread { thread_id } => {
+                let path = threads::find_path(
+                    &self.searcher,
+                    self.fields.id,
+                    self.fields.thread_path,
+                    thread_id,
+                )?;
+                Ok(Some(match path {
+                    Some(path) => Box::new(TermQuery::new(
+                        Term::from_facet(self.fields.thread_path, &path),
+                        IndexRecordOption::Basic,
+                    )),
+                    // An unknown thread has no descendants either.
+                    None => Box::new(EmptyQuery),
+                }))
+            }
+            QueryNode::DateRange { field, from, to } => {
+                let bound = |millis: &Option<f64>, bound: fn(DateTime) -> Bound<DateTime>| {
+                    millis.map_or(Bound::Unbounded, |millis| {
+                        bound(DateTime::from_timestamp_millis(millis as i64))
+                    })
+                };
+                Ok(Some(Box::new(RangeQuery::new_date_bounds(
+                    field.name().to_string(),
+                    bound(from, Bound::Included),
+                    bound(to, Bound::Excluded),
+                ))))
+            }
+            QueryNode::Bool {
+                must,
+                should,
+                must_not,
+                filter,
+            } => {
+                let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![];
+                let mut has_positive_clause = false;
+                for (occur, nodes) in [
+                    (Occur::Must, self.without_stop_words(must)),
+                    (Occur::Should, should.iter().collect()),
+                    (Occur::MustNot, must_not.iter().collect()),
+                ] {
+                    for node in nodes {
+                        if let Some(query) = self.build(node)? {
+                            has_positive_clause |= occur != Occur::MustNot;
+                            clauses.push((occur, query));
+                        }
+                    }
+                }
+                for node in filter {
+                    if let Some(query) = self.build(node)? {
+                        has_positive_clause = true;
+                        clauses.push((Occur::Must, Box::new(ConstScoreQuery::new(query, 0.0))));
+                    }
+                }
+
+                if clauses.is_empty() {
+                    return Ok(None);
+                }
+                // A boolean query made only of exclusions matches nothing,
+                // so "-draft" is read as "everything except drafts".
+                if !has_positive_clause {
+                    clauses.push((Occur::Must, Box::new(AllQuery)));
+                }
+
+                Ok(Some(Box::new(BooleanQuery::new(clauses))))
+            }
+        }
+    }
+
+    /// Replaces the raw text of the search box in the tree by the tree
+    /// parsed from it, as it is searched by `build`.
+    pub fn expand(&self, node: &QueryNode) -> anyhow::Result<QueryNode> {
+        let expand_all = |nodes: &Vec<QueryNode>| -> anyhow::Result<Vec<QueryNode>> {
+            nodes.iter().map(|node| self.expand(node)).collect()
+        };
+        match node {
+            QueryNode::Text { text } => self.parse_text(text),
+            QueryNode::Bool {
+                must,
+                should,
+                must_not,
+                filter,
+            } => Ok(QueryNode::Bool {
+                must: expand_all(must)?,
+                should: expand_all(should)?,
+                must_not: expand_all(must_not)?,
+                filter: expand_all(filter)?,
+            }),
+            node => Ok(node.clone()),
+        }
+    }
+
+    /// Tokens searched for the words of the tree, in the order of the tree.
+    pub fn tokens(&self, node: &QueryNode) -> anyhow::Result<Vec<String>> {
+        let texts = match node {
+            QueryNode::Text { .. } => return self.tokens(&self.expand(node)?),
+            QueryNode::Term { text, .. }
+            | QueryNode::Phrase { text, .. }
+            | QueryNode::Prefix { text, .. } => vec![text.clone()],
+            QueryNode::Near { texts, .. } => texts.clone(),
+            QueryNode::Bool {
+                must,
+                should,
+                must_not,
+                filter,
+            } => {
+                let mut tokens = vec![];
+                let must = self.without_stop_words(must);
+                for node in must.into_iter().chain(should).chain(must_not).chain(filter) {
+                    tokens.extend(self.tokens(node)?);
+                }
+                return Ok(tokens);
+            }
+            _ => vec![],
+        };
+        Ok(texts
+            .iter()
+            .flat_map(|text| self.tokenize(&normalize(text)))
+            .map(|(_, token)| token)
+            .collect())
+    }
+
+    // Drops plain words which are stop words, unless every required word is
+    // one. Quoted phrases and scoped words keep their stop words.
+    fn without_stop_words<'a>(&self, must: &'a [QueryNode]) -> Vec<&'a QueryNode> {
+        let is_stop_word = |node: &QueryNode| match node {
+            QueryNode::Term { text, field: None } => self.stop_words.contains(text),
+            _ => false,
+        };
+        if must.iter().all(is_stop_word) {
+            return must.iter().collect();
+        }
+        must.iter().filter(|node| !is_stop_word(node)).collect()
+    }
+
+    fn parse_text(&self, text: &str) -> anyhow::Result<QueryNode> {
+        match self.mode {
+            SearchMode::Standard => parse(text),
+            SearchMode::Incremental => parse_incremental(text),
+        }
+    }
+
+    // A scoped query searches only the given field, otherwise
+    // every text field is searched with its boost.
+    fn fields(&self, field: &Option<QueryField>) -> Vec<(Field, f32)> {
+        match field {
+            Some(QueryField::Title) => vec![(self.fields.title, 1.0)],
+            Some(QueryField::Text) => vec![(self.fields.text, 1.0)],
+            Some(QueryField::Tags) => vec![(self.fields.tags, 1.0)],
+            None => vec![
+                (self.fields.title, self.boosts.title),
+                (self.fields.text, self.boosts.text),
+                (self.fields.tags, self.boosts.tags),
+            ],
+        }
+    }
+
+    fn combine_fields(
+        &self,
+        field: &Option<QueryField>,
+        build: impl Fn(Field) -> Option<Box<dyn Query>>,
+    ) -> Option<Box<dyn Query>> {
+        let fields = self.fields(field);
+        if fields.len() == 1 {
+            return build(fields[0].0);
+        }
+
+        let clauses = fields
+            .into_iter()
+            .filter(|(_, boost)| *boost > 0.0)
+            .filter_map(|(field, boost)| {
+                let query: Box<dyn Query> = Box::new(BoostQuery::new(build(field)?, boost));
+                Some((Occur::Should, query))
+            })
+            .collect::<Vec<(Occur, Box<dyn Query>)>>();
+        match clauses.len() {
+            0 => None,
+            _ => Some(Box::new(BooleanQuery::new(clauses))),
+        }
+    }
+
+    fn tokenize(&self, text: &str) -> Vec<(usize, String)> {
+        let mut tokens = vec![];
+        let mut analyzer = self.analyzer.clone();
+        let mut token_stream = analyzer.token_stream(text);
+        token_stream.process(&mut |token| {
+            tokens.push((token.position, token.text.clone()));
+        });
+        tokens
+    }
+
+    fn build_term(
+        &self,
+        text: &str,
+        field: &Option<QueryField>,
+        prefix: bool,
+    ) -> anyhow::Result<Option<Box<dyn Query>>> {
+        let alternatives = synonyms::find_group(&self.synonyms, text);
+        if alternatives.is_empty() {
+            return Ok(self.build_words(text, field, prefix));
+        }
+
+        let clauses = alternatives
+            .iter()
+            .filter_map(|alternative| self.build_words(alternative, field, prefix))
+            .map(|query| (Occur::Should, query))
+            .collect::<Vec<(Occur, Box<dyn Query>)>>();
+        match clauses.len() {
+            0 => Ok(None),
+            _ => Ok(Some(Box::new(BooleanQuery::new(clauses)))),
+        }
+    }
+
+    // Every token of the text is required. A single word may produce several
+    // tokens, e.g. CJK bigrams or "route162" split into "route" and "162".
+    fn build_words(
+        &self,
+        text: &str,
+        field: &Option<QueryField>,
+        prefix: bool,
+    ) -> Option<Box<dyn Query>> {
+        let tokens = self.tokenize(&normalize(text));
+        let last = tokens.len().saturating_sub(1);
+        self.combine_fields(field, |field| {
+            let clauses = tokens
+                .iter()
+                .enumerate()
+                .map(|(i, (_, token))| {
+                    let term = Term::from_field_text(field, token);
+                    let query = if prefix && i == last {
+                        self.prefix_query(term, token)
+                    } else {
+                        self.word_query(term, token)
+                    };
+                    (Occur::Must, query)
+                })
+                .collect::<Vec<(Occur, Box<dyn Query>)>>();
+
+            match clauses.len() {
+                0 => None,
+                1 => clauses.into_iter().next().map(|(_, query)| query),
+                _ => Some(Box::new(BooleanQuery::new(clauses))),
+            }
+        })
+    }
+
+    fn word_query(&self, term: Term, token: &str) -> Box<dyn Query> {
+        let distance = self.fuzziness.distance(token);
+        match self.mode {
+            SearchMode::Standard => Box::new(FuzzyTermQuery::new_prefix(term, distance, true)),
+            SearchMode::Incremental => match distance {
+                0 => Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs)),
+                // Words typed earlier are allowed at most one typo.
+                _ => Box::new(FuzzyTermQuery::new(term, 1, true)),
+            },
+        }
+    }
+
+    // Matches words starting with the token, scoring the token itself
+    // higher so that complete words rank above longer completions.
+    fn prefix_query(&self, term: Term, token: &str) -> Box<dyn Query> {
+        let exact = Box::new(TermQuery::new(term.clone(), IndexRecordOption::WithFreqs));
+        let is_cjk = token.chars().next().is_some_and(is_cjk_codepoint);
+        if !is_cjk && token.chars().count() < MIN_PREFIX_LENGTH {
+            return exact;
+        }
+
+        Box::new(BooleanQuery::new(vec![
+            (
+                Occur::Should,
+                Box::new(BoostQuery::new(exact, COMPLETE_WORD_BOOST)),
+            ),
+            (
+                Occur::Should,
+                Box::new(FuzzyTermQuery::new_prefix(term, 0, true)),
+            ),
+        ]))
+    }
+
+    fn build_pattern(
+        &self,
+        pattern: &str,
+        field: &Option<QueryField>,
+    ) -> anyhow::Result<Option<Box<dyn Query>>> {
+        self.build_regexes(pattern::split(pattern), field)
+    }
+
+    fn build_wildcard(
+        &self,
+        pattern: &str,
+        field: &Option<QueryField>,
+    ) -> anyhow::Result<Option<Box<dyn Query>>> {
+        self.build_regexes(pattern::wildcard(pattern), field)
+    }
+
+    // Patterns are matched against the words field, which holds the words
+    // of every text field as they were written, since the text fields hold
+    // stems, e.g. "failur", which patterns like "failure" do not match.
+    // Each word of the pattern is expanded to the words matching it, and
+    // every combination of them is searched as a phrase. A scoped pattern
+    // searches the stems of these words in its field.
+    fn build_regexes(
+        &self,
+        mut words: Vec<String>,
+        field: &Option<QueryField>,
+    ) -> anyhow::Result<Option<Box<dyn Query>>> {
+        // a lone `*` at either end matches any word or none
+        let any_word = String::from(".*");
+        while words.last() == Some(&any_word) {
+            words.pop();
+        }
+        let start = words.iter().take_while(|word| **word == any_word).count();
+        let regexes = words[start..]
+            .iter()
+            .map(|word| {
+                if *word == any_word {
+                    Ok(None)
+                } else {
+                    pattern::regex(word).map(|regex| Some(Arc::new(regex)))
+                }
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        match (regexes.as_slice(), field) {
+            ([], _) => return Ok(None),
+            ([Some(regex)], None) => {
+                return Ok(Some(Box::new(RegexQuery::from_regex(
+                    regex.clone(),
+                    self.fields.words,
+                ))))
+            }
+            _ => {}
+        }
+
+        let target = match field {
+            Some(_) => self.fields(field)[0].0,
+            None => self.fields.words,
+        };
+        // Parts matching too many words to search every phrase are left out
+        // of the phrases, and only required to match a word of the document,
+        // or a stem of the scoped field.
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![];
+        let mut combinations: Vec<Vec<(usize, Term)>> = vec![vec![]];
+        for (position, regex) in regexes.iter().enumerate() {
+            let Some(regex) = regex else { continue };
+            let matches = pattern::expand(&self.searcher, self.fields.words, regex)?
+                .map(|words| self.pattern_tokens(words, field))
+                .filter(|matches| combinations.len() * matches.len() <= pattern::MAX_PHRASES);
+            let Some(matches) = matches else {
+                clauses.push((
+                    Occur::Must,
+                    Box::new(RegexQuery::from_regex(regex.clone(), target)),
+                ));
+                continue;
+            };
+            combinations = combinations
+                .iter()
+                .flat_map(|terms| {
+                    matches.iter().map(move |token| {
+                        let mut terms = terms.clone();
+                        terms.push((position, Term::from_field_text(target, token)));
+                        terms
+                    })
+                })
+                .collect();
+        }
+        // a part matching no word
+        if combinations.is_empty() {
+            return Ok(Some(Box::new(EmptyQuery)));
+        }
+
+        let phrases = combinations
+            .into_iter()
+            .filter(|terms| !terms.is_empty())
+            .map(|mut terms| {
+                let query: Box<dyn Query> = match terms.len() {
+                    1 => Box::new(TermQuery::new(
+                        terms.remove(0).1,
+                        IndexRecordOption::WithFreqs,
+                    )),
+                    _ => Box::new(PhraseQuery::new_with_offset(terms)),
+                };
+                (Occur::Should, query)
+            })
+            .collect::<Vec<(Occur, Box<dyn Query>)>>();
+        if !phrases.is_empty() {
+            clauses.push((Occur::Must, Box::new(BooleanQuery::new(phrases))));
+        }
+        match clauses.len() {
+            1 => Ok(clauses.pop().map(|(_, query)| query)),
+            _ => Ok(Some(Box::new(BooleanQuery::new(clauses)))),
+        }
+    }
+
+    // Tokens searched for the words matching a part of a pattern: the words
+    // themselves, or their stems in a scoped field.
+    fn pattern_tokens(&self, words: Vec<String>, field: &Option<QueryField>) -> Vec<String> {
+        if field.is_none() {
+            return words;
+        }
+        let mut stems = vec![];
+        for word in words {
+            let stem = self.tokenize(&word).into_iter().next();
+            if let Some((_, stem)) = stem.filter(|(_, stem)| !stems.contains(stem)) {
+                stems.push(stem);
+            }
+        }
+        stems
+    }
+
+    // Tokens of the query analyzer at the positions of the same words in
+    // the index, and the number of positions they span. The index has one
+    // more token at the end of each CJK run of two characters or more, its
+    // last character alone, which the query analyzer leaves out, e.g.
+    // "東京" is indexed as "東京" and "京".
+    fn tokenize_as_indexed(&self, text: &str) -> (Vec<(usize, String)>, usize) {
+        let mut tokens = vec![];
+        let mut analyzer = self.analyzer.clone();
+        let mut token_stream = analyzer.token_stream(text);
+        token_stream.process(&mut |token| tokens.push(token.clone()));
+
+        let mut positioned = vec![];
+        let mut shift = 0;
+        for (i, token) in tokens.iter().enumerate() {
+            positioned.push((token.position + shift, token.text.clone()));
+            let is_bigram =
+                token.text.chars().count() == 2 && token.text.chars().all(is_cjk_codepoint);
+            // the next bigram of the run overlaps this one
+            let is_run_end = tokens
+                .get(i + 1)
+                .is_none_or(|next| next.offset_from >= token.offset_to);
+            if is_bigram && is_run_end {
+                shift += 1;
+            }
+        }
+        let length = tokens.last().map_or(0, |token| token.position + 1) + shift;
+        (positioned, length)
+    }
+
+    // The texts are tokenized separately and placed one after another,
+    // so that the last CJK character of a text is not paired with the
+    // first character of the next one.
+    fn build_phrase(
+        &self,
+        texts: &[&str],
+        field: &Option<QueryField>,
+        slop: u32,
+    ) -> Option<Box<dyn Query>> {
+        let mut tokens = vec![];
+        let mut start = 0;
+        for text in texts {
+            let (text_tokens, length) = self.tokenize_as_indexed(&normalize(text));
+            for (position, token) in text_tokens {
+                tokens.push((start + position, token));
+            }
+            start += length;
+        }
+        self.combine_fields(field, |field| {
+            let terms = tokens
+                .iter()
+                .map(|(position, token)| (*position, Term::from_field_text(field, token)))
+                .collect::<Vec<(usize, Term)>>();
+
+            match terms.len() {
+                0 => None,
+                1 => terms.into_iter().next().map(|(_, term)| {
+                    Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs)) as Box<dyn Query>
+                }),
+                _ => Some(Box::new(PhraseQuery::new_with_offset_and_slop(terms, slop))),
+            }
+        })
+    }
+}
+
+/// Parses the text of the search box into a query tree.
+/// This never fails on user input: unknown syntax is searched as plain words.
+///
+/// Supported syntax:
+/// - `a b` both words are required
+/// - `a OR b` either word
+/// - `-a` / `NOT a` excludes documents
+/// - `"a b"` phrase, `"a b"~2` allowing 2 extra positions between the words
+/// - `a NEAR/5 b` both words within 5 positions of each other
+/// - `(a OR b) c` grouping
+/// - `title:a` field scope (`title`, `text` or `tags`)
+/// - `type:card` restricts the document type
+pub fn parse(text: &str) -> anyhow::Result<QueryNode> {
+    let mut tokens = lex(text).into_iter();
+    parse_group(&mut tokens, 0)
+}
+
+/// Parses the text of the search box as it is being typed: the last word
+/// becomes a `Prefix` node, which is never removed as a stop word since
+/// it may be the beginning of a longer word, e.g. "the" of "theory".
+pub fn parse_incremental(text: &str) -> anyhow::Result<QueryNode> {
+    let mut tokens = lex(text);
+    // The last word is still being typed unless it is followed by
+    // a space or closed by a quote or a parenthesis.
+    if text.ends_with(char::is_alphanumeric) {
+        if let Some(token) = tokens.pop() {
+            tokens.push(match token {
+                Token::Word(word) => Token::Prefix(word),
+                Token::Scoped(scope, token) => match *token {
+                    Token::Word(word) => Token::Scoped(scope, Box::new(Token::Prefix(word))),
+                    token => Token::Scoped(scope, Box::new(token)),
+                },
+                token => token,
+            });
+        }
+    }
+    parse_group(&mut tokens.into_iter(), 0)
+}
+
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Plus,
+    Word(String),
+    // last word of the text, being typed
+    Prefix(String),
+    Phrase(String, u32),
+    Near(u32),
+    Scoped(String, Box<Token>),
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || c == '(' || c == ')' || c == '"'
+}
+
+fn read_phrase(chars: &mut Peekable<Chars>) -> String {
+    // opening quote
+    chars.next();
+    let mut phrase = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            break;
+        }
+        phrase.push(c);
+    }
+    phrase
+}
+
+// Reads the slop following a phrase, e.g. `"a b"~2`.
+fn read_slop(chars: &mut Peekable<Chars>) -> u32 {
+    if chars.peek() != Some(&'~') {
+        return 0;
+    }
+    let mut lookahead = chars.clone();
+    lookahead.next();
+    let mut digits = String::new();
+    while let Some(c) = lookahead.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    match digits.parse() {
+        Ok(slop) => {
+            *chars = lookahead;
+            slop
+        }
+        Err(_) => 0,
+    }
+}
+
+fn lex(text: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' {
+            chars.next();
+            tokens.push(Token::LParen);
+        } else if c == ')' {
+            chars.next();
+            tokens.push(Token::RParen);
+        } else if c == '"' {
+            let phrase = read_phrase(&mut chars);
+            tokens.push(Token::Phrase(phrase, read_slop(&mut chars)));
+        } else if (c == '-' || c == '+') && {
+            let mut lookahead = chars.clone();
+            lookahead.next();
+            matches!(lookahead.peek(), Some(&next) if !next.is_whitespace())
+        } {
+            chars.next();
+            tokens.push(if c == '-' { Token::Not } else { Token::Plus });
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if is_delimiter(c) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+
+            let token = match word.as_str() {
+                "AND" | "&&" => Token::And,
+                "OR" | "||" => Token::Or,
+                "NOT" => Token::Not,
+                word if word.starts_with("NEAR/") => match word["NEAR/".len()..].parse() {
+                    Ok(distance) => Token::Near(distance),
+                    Err(_) => Token::Word(word.to_string()),
+                },
+                _ => match word.split_once(':') {
+                    Some((scope, "")) if chars.peek() == Some(&'"') => {
+                        let phrase = read_phrase(&mut chars);
+                        let slop = read_slop(&mut chars);
+                        Token::Scoped(scope.to_string(), Box::new(Token::Phrase(phrase, slop)))
+                    }
+                    Some((scope, rest)) if !scope.is_empty() && !rest.is_empty() => {
+                        Token::Scoped(scope.to_string(), Box::new(Token::Word(rest.to_string())))
+                    }
+                    _ => Token::Word(word),
+                },
+            };
+            tokens.push(token);
+        }
+    }
+
+    tokens
+}
+
+enum Clause {
+    Must(QueryNode),
+    MustNot(QueryNode),
+    Filter(QueryNode),
+    Or(Vec<QueryNode>),
+}
+
+// Groups are parsed recursively, so deeper parentheses are read as plain
+// characters to keep a text like "((((…" from overflowing the stack.
+const MAX_DEPTH: usize = 32;
+
+fn parse_group(tokens: &mut std::vec::IntoIter<Token>, depth: usize) -> anyhow::Result<QueryNode> {
+    let mut clauses: Vec<Clause> = vec![];
+    let mut negate = false;
+    let mut or = false;
+    let mut near = None;
+    // parentheses opened beyond MAX_DEPTH, read as plain characters
+    let mut ignored = 0;
+
+    while let Some(token) = tokens.next() {
+        let node = match token {
+            Token::LParen if depth == MAX_DEPTH => {
+                ignored += 1;
+                continue;
+            }
+            Token::RParen if ignored > 0 => {
+                ignored -= 1;
+                continue;
+            }
+            Token::RParen if depth > 0 => break,
+            // unbalanced parenthesis is ignored
+            Token::RParen => continue,
+            Token::And | Token::Plus => continue,
+            Token::Or => {
+                or = true;
+                continue;
+            }
+            Token::Not => {
+                negate = true;
+                continue;
+            }
+            Token::Near(distance) => {
+                near = Some(distance);
+                continue;
+            }
+            Token::LParen => parse_group(tokens, depth + 1)?,
+            token => match to_leaf(token) {
+                Some(Leaf::Filter(node)) if !negate => {
+                    clauses.push(Clause::Filter(node));
+                    or = false;
+                    continue;
+                }
+                Some(Leaf::Filter(node)) | Some(Leaf::Query(node)) => node,
+                None => continue,
+            },
+        };
+
+        if negate {
+            clauses.push(Clause::MustNot(node));
+        } else if let Some(distance) = near {
+            match clauses.pop() {
+                Some(Clause::Must(previous)) => match join_near(&previous, &node, distance) {
+                    Some(near) => clauses.push(Clause::Must(near)),
+                    // not words, e.g. a group; read as both required
+                    None => {
+                        clauses.push(Clause::Must(previous));
+                        clauses.push(Clause::Must(node));
+                    }
+                },
+                Some(clause) => {
+                    clauses.push(clause);
+                    clauses.push(Clause::Must(node));
+                }
+                None => clauses.push(Clause::Must(node)),
+            }
+        } else if or {
+            match clauses.pop() {
+                Some(Clause::Must(previous)) => clauses.push(Clause::Or(vec![previous, node])),
+                Some(Clause::Or(mut nodes)) => {
+                    nodes.push(node);
+                    clauses.push(Clause::Or(nodes));
+                }
+                Some(clause) => {
+                    clauses.push(clause);
+                    clauses.push(Clause::Must(node));
+                }
+                None => clauses.push(Clause::Must(node)),
+            }
+        } else {
+            clauses.push(Clause::Must(node));
+        }
+        negate = false;
+        or = false;
+        near = None;
+    }
+
+    let mut must = vec![];
+    let mut must_not = vec![];
+    let mut filter = vec![];
+    for clause in clauses {
+        match clause {
+            Clause::Must(node) => must.push(node),
+            Clause::MustNot(node) => must_not.push(node),
+            Clause::Filter(node) => filter.push(node),
+            Clause::Or(nodes) => must.push(QueryNode::Bool {
+                must: vec![],
+                should: nodes,
+                must_not: vec![],
+                filter: vec![],
+            }),
+        }
+    }
+
+    Ok(QueryNode::Bool {
+        must,
+        should: vec![],
+        must_not,
+        filter,
+    })
+}
+
+// Texts of a node which can be an operand of `NEAR`,
+// with the field they are scoped to and the slop of the node.
+fn near_operand(node: &QueryNode) -> Option<(Vec<String>, Option<QueryField>, u32)> {
+    match node {
+        QueryNode::Term { text, field } => Some((vec![text.clone()], *field, 0)),
+        QueryNode::Phrase { text, field, slop } => Some((vec![text.clone()], *field, *slop)),
+        QueryNode::Near {
+            texts,
+            field,
+            distance,
+        } => Some((texts.clone(), *field, *distance)),
+        _ => None,
+    }
+}
+
+// `a NEAR/2 b NEAR/3 c` is a single `Near` node with the largest distance.
+fn join_near(previous: &QueryNode, node: &QueryNode, distance: u32) -> Option<QueryNode> {
+    match (near_operand(previous), near_operand(node)) {
+        (Some((mut texts, field, previous_slop)), Some((next_texts, next_field, next_slop)))
+            if field == next_field =>
+        {
+            texts.extend(next_texts);
+            Some(QueryNode::Near {
+                texts,
+                field,
+                distance: distance.max(previous_slop).max(next_slop),
+            })
+        }
+        _ => None,
+    }
+}
+
+enum Leaf {
+    Query(QueryNode),
+    Filter(QueryNode),
+}
+
+fn to_leaf(token: Token) -> Option<Leaf> {
+    match token {
+        Token::Word(text) => Some(Leaf::Query(QueryNode::Term { text, field: None })),
+        Token::Prefix(text) => Some(Leaf::Query(QueryNode::Prefix { text, field: None })),
+        Token::Phrase(text, slop) => Some(Leaf::Query(QueryNode::Phrase {
+            text,
+            field: None,
+            slop,
+        })),
+        Token::Scoped(scope, token) => {
+            if scope == "type" || scope == "thread" {
+                let text = match *token {
+                    Token::Word(text) | Token::Prefix(text) | Token::Phrase(text, _) => text,
+                    _ => return None,
+                };
+                return Some(Leaf::Filter(match scope.as_str() {
+                    "type" => QueryNode::DocType { doc_type: text },
+                    _ => QueryNode::Thread { thread_id: text },
+                }));
+            }
+
+            match (QueryField::from_name(&scope), *token) {
+                (Some(field), Token::Word(text)) => Some(Leaf::Query(QueryNode::Term {
+                    text,
+                    field: Some(field),
+                })),
+                (Some(field), Token::Prefix(text)) => Some(Leaf::Query(QueryNode::Prefix {
+                    text,
+                    field: Some(field),
+                })),
+                (Some(field), Token::Phrase(text, slop)) => Some(Leaf::Query(QueryNode::Phrase {
+                    text,
+                    field: Some(field),
+                    slop,
+                })),
+                // not a field scope, e.g. "10:30" or "https://..."
+                (None, Token::Word(text)) => Some(Leaf::Query(QueryNode::Term {
+                    text: format!("{}:{}", scope, text),
+                    field: None,
+                })),
+                (None, Token::Prefix(text)) => Some(Leaf::Query(QueryNode::Prefix {
+                    text: format!("{}:{}", scope, text),
+                    field: None,
+                })),
+                (None, Token::Phrase(text, slop)) => Some(Leaf::Query(QueryNode::Phrase {
+                    text: format!("{}: {}", scope, text),
+                    field: None,
+                    slop,
+                })),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn term(text: &str) -> QueryNode {
+        QueryNode::Term {
+            text: String::from(text),
+            field: None,
+        }
+    }
+
+    fn must(nodes: Vec<QueryNode>) -> QueryNode {
+        QueryNode::Bool {
+            must: nodes,
+            should: vec![],
+            must_not: vec![],
+            filter: vec![],
+        }
+    }
+
+    #[test]
+    fn test() {
+        assert_eq!(
+            parse("羽田 airport").unwrap(),
+            must(vec![term("羽田"), term("airport")])
+        );
+
+        assert_eq!(
+            parse("a OR b c").unwrap(),
+            must(vec![
+                QueryNode::Bool {
+                    must: vec![],
+                    should: vec![term("a"), term("b")],
+                    must_not: vec![],
+                    filter: vec![],
+                },
+                term("c")
+            ])
+        );
+
+        assert_eq!(
+            parse("deploy -failure type:card thread:abc").unwrap(),
+            QueryNode::Bool {
+                must: vec![term("deploy")],
+                should: vec![],
+                must_not: vec![term("failure")],
+                filter: vec![
+                    QueryNode::DocType {
+                        doc_type: String::from("card")
+                    },
+                    QueryNode::Thread {
+                        thread_id: String::from("abc")
+                    }
+                ],
+            }
+        );
+
+        assert_eq!(
+            parse("text:\"brûlée connection\" (x").unwrap(),
+            must(vec![
+                QueryNode::Phrase {
+                    text: String::from("brûlée connection"),
+                    field: Some(QueryField::Text),
+                    slop: 0,
+                },
+                must(vec![term("x")])
+            ])
+        );
+
+        // never fails on stray syntax
+        assert_eq!(
+            parse("a:b: ) OR \"c").unwrap(),
+            must(vec![QueryNode::Bool {
+                must: vec![],
+                should: vec![
+                    term("a:b:"),
+                    QueryNode::Phrase {
+                        text: String::from("c"),
+                        field: None,
+                        slop: 0,
+                    },
+                ],
+                must_not: vec![],
+                filter: vec![],
+            }])
+        );
+        assert_eq!(
+            parse("deploy NEAR/5 failure NEAR/2 \"roll out\"~3 NEAR/x").unwrap(),
+            must(vec![
+                QueryNode::Near {
+                    texts: vec![
+                        String::from("deploy"),
+                        String::from("failure"),
+                        String::from("roll out")
+                    ],
+                    field: None,
+                    distance: 5,
+                },
+                term("NEAR/x")
+            ])
+        );
+        assert_eq!(
+            parse("title:a NEAR/1 b \"c\"~").unwrap(),
+            must(vec![
+                QueryNode::Term {
+                    text: String::from("a"),
+                    field: Some(QueryField::Title),
+                },
+                term("b"),
+                QueryNode::Phrase {
+                    text: String::from("c"),
+                    field: None,
+                    slop: 0,
+                },
+                term("~")
+            ])
+        );
+
+        assert_eq!(parse("").unwrap(), must(vec![]));
+        assert_eq!(
+            parse("- + ::").unwrap(),
+            must(vec![term("-"), term("+"), term("::")])
+        );
+
+        // only the word being typed is a prefix
+        let prefix = |text: &str, field: Option<QueryField>| QueryNode::Prefix {
+            text: String::from(text),
+            field,
+        };
+        assert_eq!(
+            parse_incremental("deploy fail").unwrap(),
+            must(vec![term("deploy"), prefix("fail", None)])
+        );
+        assert_eq!(
+            parse_incremental("deploy -fail").unwrap(),
+            QueryNode::Bool {
+                must: vec![term("deploy")],
+                should: vec![],
+                must_not: vec![prefix("fail", None)],
+                filter: vec![],
+            }
+        );
+        assert_eq!(
+            parse_incremental("deploy type:card").unwrap(),
+            QueryNode::Bool {
+                must: vec![term("deploy")],
+                should: vec![],
+                must_not: vec![],
+                filter: vec![QueryNode::DocType {
+                    doc_type: String::from("card")
+                }],
+            }
+        );
+        assert_eq!(
+            parse_incremental("deploy title:fail").unwrap(),
+            must(vec![
+                term("deploy"),
+                prefix("fail", Some(QueryField::Title))
+            ])
+        );
+        assert_eq!(
+            parse_incremental("deploy fail ").unwrap(),
+            must(vec![term("deploy"), term("fail")])
+        );
+        assert_eq!(
+            parse_incremental("(deploy fail)").unwrap(),
+            must(vec![must(vec![term("deploy"), term("fail")])])
+        );
+
+        // parentheses nested deeper than MAX_DEPTH are ignored
+        let nested = |depth: usize| format!("{}a{} b", "(".repeat(depth), ")".repeat(depth));
+        let mut expected = term("a");
+        for _ in 0..MAX_DEPTH {
+            expected = must(vec![expected]);
+        }
+        assert_eq!(
+            parse(&nested(100_000)).unwrap(),
+            must(vec![expected, term("b")])
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::cmp::Reverse;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tantivy::columnar::{Column, StrColumn};
+use tantivy::{DateTime, DocId, Score, SegmentReader};
+
+pub const CREATED_AT: &str = "created_at";
+pub const UPDATED_AT: &str = "updated_at";
+pub const OUTLINE: &str = "outline";
+
+// Sorts lower than any character of a fractional index, so that a thread
+// comes right before its children and its children before its next sibling.
+const OUTLINE_SEPARATOR: char = '\u{0}';
+
+const DAY_IN_MILLIS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+// Computes the sort key of each document of a segment.
+type SegmentTweaker<T> = Box<dyn FnMut(DocId, Score) -> T>;
+
+/// Order of the search results. Date orders list the newest documents first.
+/// `Outline` lists documents in the order of the thread tree, as `getNode`
+/// orders children by `fractional_index`.
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum SortBy {
+    Relevance,
+    CreatedAt,
+    UpdatedAt,
+    Outline,
+}
+
+/// Multiplies the relevance score of recently updated documents by up to
+/// `1 + weight`. The extra weight halves every `half_life_days`.
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct RecencyBoost {
+    pub weight: f32,
+    pub half_life_days: f32,
+}
+
+impl RecencyBoost {
+    fn factor(&self, updated_at: Option<DateTime>, now: i64) -> Score {
+        let Some(updated_at) = updated_at else {
+            return 1.0;
+        };
+        if self.half_life_days <= 0.0 {
+            return 1.0;
+        }
+        // Documents dated in the future are treated as just updated.
+        let age_in_days = (now - updated_at.into_timestamp_millis()).max(0) as f64 / DAY_IN_MILLIS;
+        let decay = 0.5_f64.powf(age_in_days / self.half_life_days as f64);
+        1.0 + self.weight * decay as Score
+    }
+}
+
+pub fn now_in_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Sort key of a document from the fractional indexes of its ancestors
+/// and of itself, from the root down.
+pub fn outline_key<S: AsRef<str>>(path: &[S]) -> String {
+    path.iter()
+        .map(|index| index.as_ref())
+        .collect::<Vec<&str>>()
+        .join(&OUTLINE_SEPARATOR.to_string())
+}
+
+pub fn split_outline_key(key: &str) -> Vec<&str> {
+    key.split(OUTLINE_SEPARATOR).collect()
+}
+
+// A segment without any dated document has no column for the field.
+fn date_column(segment_reader: &SegmentReader, field: &str) -> Option<Column<DateTime>> {
+    segment_reader.fast_fields().date(field).ok()
+}
+
+/// Score tweaker for `TopDocs::tweak_score` blending the recency into the score.
+pub fn recency_tweaker(
+    boost: RecencyBoost,
+) -> impl Fn(&SegmentReader) -> SegmentTweaker<Score> + Send + Sync {
+    let now = now_in_millis();
+    move |segment_reader| {
+        let column = date_column(segment_reader, UPDATED_AT);
+        Box::new(move |doc, score| {
+            let updated_at = column.as_ref().and_then(|column| column.first(doc));
+            score * boost.factor(updated_at, now)
+        })
+    }
+}
+
+/// Score tweaker for `TopDocs::tweak_score` replacing the score by the date,
+/// so that the newest documents come first and undated ones come last.
+pub fn date_tweaker(
+    field: &'static str,
+) -> impl Fn(&SegmentReader) -> SegmentTweaker<i64> + Send + Sync {
+    move |segment_reader| {
+        let column = date_column(segment_reader, field);
+        Box::new(move |doc, _| {
+            column
+                .as_ref()
+                .and_then(|column| column.first(doc))
+                .map_or(i64::MIN, |date| date.into_timestamp_millis())
+        })
+    }
+}
+
+/// Score tweaker for `TopDocs::tweak_score` replacing the score by the outline
+/// key. Keys are compared in reverse so that the top documents come first,
+/// and documents without a key come last.
+pub fn outline_tweaker(
+) -> impl Fn(&SegmentReader) -> SegmentTweaker<(bool, Reverse<String>)> + Send + Sync {
+    move |segment_reader| {
+        let column: Option<StrColumn> = segment_reader.fast_fields().str(OUTLINE).ok().flatten();
+        Box::new(move |doc, _| {
+            let mut key = String::new();
+            let found = column.as_ref().is_some_and(|column| {
+                column
+                    .term_ords(doc)
+                    .next()
+                    .is_some_and(|ord| column.ord_to_str(ord, &mut key).unwrap_or(false))
+            });
+            (found, Reverse(key))
+        })
+    }
+}
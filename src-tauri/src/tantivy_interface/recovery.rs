@@ -0,0 +1,205 @@
+use super::locking::IndexLockedError;
+use super::ranking::now_in_millis;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tantivy::directory::error::LockError;
+use tantivy::directory::{Directory, ManagedDirectory, MmapDirectory, INDEX_WRITER_LOCK};
+use tantivy::schema::Schema;
+use tantivy::{Index, IndexSettings, SegmentComponent, TantivyError};
+
+// Event emitted with an `IndexRecreated` once the index is initialized.
+pub const RECREATED_EVENT: &str = "index-recreated";
+
+/// Sent when the index was recreated empty, so that the frontend indexes
+/// every card and thread of the database again.
+/// `quarantine_path` holds the files of a corrupted index, kept for
+/// inspection, and is `None` when the index only had an older schema.
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IndexRecreated {
+    pub reason: String,
+    pub quarantine_path: Option<String>,
+}
+
+fn open_directory(path: &Path) -> anyhow::Result<ManagedDirectory> {
+    if !path.exists() {
+        fs::create_dir_all(path)?;
+    }
+    Ok(ManagedDirectory::wrap(Box::new(MmapDirectory::open(
+        path,
+    )?))?)
+}
+
+fn create(path: &Path, schema: Schema) -> anyhow::Result<Index> {
+    let directory = open_directory(path)?;
+    Ok(Index::create(directory, schema, IndexSettings::default())?)
+}
+
+// Checks that the files of every segment exist and can be opened, so that
+// files deleted by hand are found at startup rather than by the first
+// search. Their content is not checksummed, which would read the whole
+// index on every startup.
+fn validate(index: &Index) -> anyhow::Result<()> {
+    let mut missing_files = vec![];
+    for segment_meta in index.searchable_segment_metas()? {
+        for component in SegmentComponent::iterator() {
+            let is_used = match component {
+                SegmentComponent::TempStore => false,
+                SegmentComponent::Delete => segment_meta.has_deletes(),
+                _ => true,
+            };
+            let file = segment_meta.relative_path(*component);
+            if is_used && !index.directory().exists(&file)? {
+                missing_files.push(file);
+            }
+        }
+    }
+    if !missing_files.is_empty() {
+        return Err(anyhow!("Missing index files: {:?}", missing_files));
+    }
+    index.reader()?;
+    Ok(())
+}
+
+// Probes the writer lock of the index, which is released right away.
+fn is_locked(path: &Path) -> anyhow::Result<bool> {
+    match MmapDirectory::open(path)?.acquire_lock(&INDEX_WRITER_LOCK) {
+        Ok(_) => Ok(false),
+        Err(LockError::LockBusy) => Ok(true),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn is_outdated_schema(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<TantivyError>(),
+        Some(TantivyError::SchemaError(_))
+    )
+}
+
+fn quarantine(path: &Path) -> anyhow::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or(anyhow!("The index directory has no name"))?
+        .to_string_lossy();
+    let quarantine_path = path.with_file_name(format!("{}.corrupted-{}", name, now_in_millis()));
+    fs::rename(path, &quarantine_path)?;
+    Ok(quarantine_path)
+}
+
+/// Opens the index at the path, or creates it. An index which can't be
+/// opened is moved aside and replaced by an empty one, unless another
+/// process holds its writer lock, which fails with `IndexLockedError`.
+pub fn open(path: &Path, schema: Schema) -> anyhow::Result<(Index, Option<IndexRecreated>)> {
+    let opened = open_directory(path)
+        .and_then(|directory| Ok(Index::open_or_create(directory, schema.clone())?));
+    let error = match opened {
+        Ok(index) => match validate(&index) {
+            Ok(()) => return Ok((index, None)),
+            Err(e) => e,
+        },
+        Err(e) => e,
+    };
+
+    // The files of an index being written by another process are left
+    // alone, as they may be replaced by its commit at any time.
+    if is_locked(path)? {
+        return Err(error.context(IndexLockedError));
+    }
+
+    // The index was created with an older schema. It is discarded, and
+    // the frontend indexes every document again on `RECREATED_EVENT`.
+    if is_outdated_schema(&error) {
+        fs::remove_dir_all(path)?;
+        let recreated = IndexRecreated {
+            reason: error.to_string(),
+            quarantine_path: None,
+        };
+        return Ok((create(path, schema)?, Some(recreated)));
+    }
+
+    let quarantine_path = quarantine(path)?;
+    let recreated = IndexRecreated {
+        reason: error.to_string(),
+        quarantine_path: Some(quarantine_path.to_string_lossy().to_string()),
+    };
+    Ok((create(path, schema)?, Some(recreated)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tantivy::schema::TEXT;
+    use tantivy::{doc, IndexWriter};
+
+    fn schema(field: &str) -> Schema {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_text_field(field, TEXT);
+        schema_builder.build()
+    }
+
+    #[test]
+    fn test() {
+        let path = std::env::temp_dir().join(format!("potrin-recovery-{}", now_in_millis()));
+
+        let (index, recreated) = open(&path, schema("text")).unwrap();
+        assert!(recreated.is_none());
+        drop(index);
+        let (index, recreated) = open(&path, schema("text")).unwrap();
+        assert!(recreated.is_none());
+        drop(index);
+
+        // unreadable metadata
+        fs::write(path.join("meta.json"), "{").unwrap();
+        let lock = MmapDirectory::open(&path)
+            .unwrap()
+            .acquire_lock(&INDEX_WRITER_LOCK)
+            .unwrap();
+        let error = open(&path, schema("text")).err().unwrap();
+        assert!(error.is::<IndexLockedError>());
+        assert_eq!(fs::read_to_string(path.join("meta.json")).unwrap(), "{");
+        drop(lock);
+        let (index, recreated) = open(&path, schema("text")).unwrap();
+        drop(index);
+        let quarantine_path = PathBuf::from(recreated.unwrap().quarantine_path.unwrap());
+        assert_eq!(
+            fs::read_to_string(quarantine_path.join("meta.json")).unwrap(),
+            "{"
+        );
+        assert!(path.join("meta.json").exists());
+        fs::remove_dir_all(&quarantine_path).unwrap();
+
+        // segment file deleted by hand
+        let (index, _) = open(&path, schema("text")).unwrap();
+        let text_field = index.schema().get_field("text").unwrap();
+        let mut writer: IndexWriter = index.writer(15_000_000).unwrap();
+        writer.add_document(doc!(text_field => "a")).unwrap();
+        writer.commit().unwrap();
+        drop(writer);
+        drop(index);
+        let store_file = fs::read_dir(&path)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|file| {
+                file.extension()
+                    .is_some_and(|extension| extension == "store")
+            })
+            .unwrap();
+        fs::remove_file(store_file).unwrap();
+        let (index, recreated) = open(&path, schema("text")).unwrap();
+        assert_eq!(index.searchable_segment_metas().unwrap().len(), 0);
+        drop(index);
+        let quarantine_path = PathBuf::from(recreated.unwrap().quarantine_path.unwrap());
+
+        // older schema
+        let (index, recreated) = open(&path, schema("title")).unwrap();
+        drop(index);
+        assert!(recreated.unwrap().quarantine_path.is_none());
+
+        fs::remove_dir_all(&path).unwrap();
+        fs::remove_dir_all(&quarantine_path).unwrap();
+    }
+}
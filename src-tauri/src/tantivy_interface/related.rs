@@ -0,0 +1,56 @@
+use super::query::SearchFields;
+use tantivy::query::{BooleanQuery, ConstScoreQuery, MoreLikeThisQuery, Occur, Query, TermQuery};
+use tantivy::schema::{IndexRecordOption, OwnedValue, Term};
+use tantivy::TantivyDocument;
+
+// Notes are short, so a word used once already characterizes a card.
+// A word must appear in another document to relate them.
+const MIN_TERM_FREQUENCY: usize = 1;
+const MIN_DOC_FREQUENCY: u64 = 2;
+
+/// Builds a query matching the cards whose text is similar to the text
+/// of the given document, excluding the document itself.
+/// `exclude_thread` also excludes the thread of the document and its subthreads.
+pub fn query(
+    document: &TantivyDocument,
+    id: &str,
+    fields: &SearchFields,
+    exclude_thread: bool,
+) -> Box<dyn Query> {
+    let text = document
+        .get_all(fields.text)
+        .cloned()
+        .collect::<Vec<OwnedValue>>();
+    let more_like_this = MoreLikeThisQuery::builder()
+        .with_min_term_frequency(MIN_TERM_FREQUENCY)
+        .with_min_doc_frequency(MIN_DOC_FREQUENCY)
+        .with_document_fields(vec![(fields.text, text)]);
+    let cards = TermQuery::new(
+        Term::from_field_text(fields.doc_type, "card"),
+        IndexRecordOption::Basic,
+    );
+    let itself = TermQuery::new(
+        Term::from_field_text(fields.id, id),
+        IndexRecordOption::Basic,
+    );
+
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![
+        (Occur::Must, Box::new(more_like_this)),
+        (
+            Occur::Must,
+            Box::new(ConstScoreQuery::new(Box::new(cards), 0.0)),
+        ),
+        (Occur::MustNot, Box::new(itself)),
+    ];
+    if exclude_thread {
+        if let Some(OwnedValue::Facet(path)) = document.get_first(fields.thread_path) {
+            let thread = TermQuery::new(
+                Term::from_facet(fields.thread_path, path),
+                IndexRecordOption::Basic,
+            );
+            clauses.push((Occur::MustNot, Box::new(thread)));
+        }
+    }
+
+    Box::new(BooleanQuery::new(clauses))
+}
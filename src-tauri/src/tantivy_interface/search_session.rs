@@ -0,0 +1,139 @@
+use anyhow::anyhow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::{SegmentOrdinal, SegmentReader, TantivyError};
+
+pub const CANCELLED: &str = "Search was cancelled";
+
+// Cancellation flag of the latest in-flight search of each session.
+// A session is typically a window or a search box of the frontend.
+static SESSIONS: OnceLock<Mutex<HashMap<String, Arc<AtomicBool>>>> = OnceLock::new();
+
+fn sessions() -> &'static Mutex<HashMap<String, Arc<AtomicBool>>> {
+    SESSIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Handle of a running search. Starting a search in a session cancels
+/// the search previously started in the same session.
+pub struct SearchSession {
+    session_id: Option<String>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl SearchSession {
+    pub fn start(session_id: Option<String>) -> anyhow::Result<SearchSession> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        if let Some(session_id) = &session_id {
+            let mut sessions = sessions().lock().map_err(|e| anyhow!(e.to_string()))?;
+            if let Some(previous) = sessions.insert(session_id.clone(), cancelled.clone()) {
+                previous.store(true, Ordering::Relaxed);
+            }
+        }
+
+        Ok(SearchSession {
+            session_id,
+            cancelled,
+        })
+    }
+
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(anyhow!(CANCELLED));
+        }
+        Ok(())
+    }
+
+    /// Wraps the collector so that the search stops before the next segment
+    /// once it is cancelled.
+    pub fn collector<C: Collector>(&self, collector: C) -> CancellableCollector<C> {
+        CancellableCollector {
+            inner: collector,
+            cancelled: self.cancelled.clone(),
+        }
+    }
+}
+
+impl Drop for SearchSession {
+    fn drop(&mut self) {
+        let Some(session_id) = &self.session_id else {
+            return;
+        };
+        if let Ok(mut sessions) = sessions().lock() {
+            if sessions
+                .get(session_id)
+                .is_some_and(|cancelled| Arc::ptr_eq(cancelled, &self.cancelled))
+            {
+                sessions.remove(session_id);
+            }
+        }
+    }
+}
+
+pub fn cancel(session_id: &str) -> anyhow::Result<()> {
+    let mut sessions = sessions().lock().map_err(|e| anyhow!(e.to_string()))?;
+    if let Some(cancelled) = sessions.remove(session_id) {
+        cancelled.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+pub struct CancellableCollector<C> {
+    inner: C,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<C: Collector> Collector for CancellableCollector<C> {
+    type Fruit = C::Fruit;
+    type Child = C::Child;
+
+    fn for_segment(
+        &self,
+        segment_local_id: SegmentOrdinal,
+        segment: &SegmentReader,
+    ) -> tantivy::Result<Self::Child> {
+        if self.cancelled.load(Ordering::Relaxed) {
+            return Err(TantivyError::InternalError(CANCELLED.to_string()));
+        }
+        self.inner.for_segment(segment_local_id, segment)
+    }
+
+    fn requires_scoring(&self) -> bool {
+        self.inner.requires_scoring()
+    }
+
+    fn merge_fruits(
+        &self,
+        segment_fruits: Vec<<Self::Child as SegmentCollector>::Fruit>,
+    ) -> tantivy::Result<Self::Fruit> {
+        self.inner.merge_fruits(segment_fruits)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let first = SearchSession::start(Some(String::from("main"))).unwrap();
+        let other = SearchSession::start(Some(String::from("other"))).unwrap();
+        let unnamed = SearchSession::start(None).unwrap();
+        assert!(first.check().is_ok());
+
+        // a newer search in the same session supersedes the older one
+        let second = SearchSession::start(Some(String::from("main"))).unwrap();
+        assert_eq!(first.check().unwrap_err().to_string(), CANCELLED);
+        assert!(second.check().is_ok());
+        assert!(other.check().is_ok());
+
+        // finishing a superseded search keeps the newer one registered
+        drop(first);
+        cancel("main").unwrap();
+        assert!(second.check().is_err());
+        assert!(other.check().is_ok());
+        assert!(unnamed.check().is_ok());
+    }
+}
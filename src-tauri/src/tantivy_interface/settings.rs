@@ -0,0 +1,37 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, Wry};
+use tauri_plugin_store::{with_store, StoreCollection};
+
+const STORE_PATH: &str = "settings.json";
+
+pub fn read<T: DeserializeOwned>(app_handle: &AppHandle, key: &str) -> anyhow::Result<Option<T>> {
+    let stores = app_handle.state::<StoreCollection<Wry>>();
+    let value = with_store(
+        app_handle.clone(),
+        stores,
+        PathBuf::from(STORE_PATH),
+        |store| Ok(store.get(key).cloned()),
+    )?;
+
+    match value {
+        Some(value) => Ok(Some(serde_json::from_value::<T>(value)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn write<T: Serialize>(app_handle: &AppHandle, key: &str, value: &T) -> anyhow::Result<()> {
+    let value = serde_json::to_value(value)?;
+    let stores = app_handle.state::<StoreCollection<Wry>>();
+    with_store(
+        app_handle.clone(),
+        stores,
+        PathBuf::from(STORE_PATH),
+        |store| {
+            store.insert(key.to_string(), value)?;
+            store.save()
+        },
+    )?;
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use levenshtein_automata::{Distance, LevenshteinAutomatonBuilder, DFA};
+use std::collections::HashMap;
+use tantivy::schema::{Field, Term};
+use tantivy::Searcher;
+use tantivy_fst::Automaton;
+
+// Same as the automaton of `FuzzyTermQuery`, which is not public.
+struct Dfa(DFA);
+
+impl Automaton for Dfa {
+    type State = u32;
+
+    fn start(&self) -> u32 {
+        self.0.initial_state()
+    }
+
+    fn is_match(&self, state: &u32) -> bool {
+        matches!(self.0.distance(*state), Distance::Exact(_))
+    }
+
+    fn can_match(&self, state: &u32) -> bool {
+        *state != levenshtein_automata::SINK_STATE
+    }
+
+    fn accept(&self, state: &u32, byte: u8) -> u32 {
+        self.0.transition(*state, byte)
+    }
+}
+
+pub fn is_indexed(searcher: &Searcher, field: Field, token: &str) -> anyhow::Result<bool> {
+    Ok(searcher.doc_freq(&Term::from_field_text(field, token))? > 0)
+}
+
+/// Returns the indexed term of the field which is the closest to the token
+/// within the distance, preferring the terms found in more documents.
+pub fn correct(
+    searcher: &Searcher,
+    field: Field,
+    token: &str,
+    distance: u8,
+) -> anyhow::Result<Option<String>> {
+    if distance == 0 {
+        return Ok(None);
+    }
+
+    let builder = LevenshteinAutomatonBuilder::new(distance, true);
+    let mut doc_freqs: HashMap<String, u64> = HashMap::new();
+    for segment_reader in searcher.segment_readers() {
+        let inverted_index = segment_reader.inverted_index(field)?;
+        let automaton = Dfa(builder.build_dfa(token));
+        let mut stream = inverted_index.terms().search(automaton).into_stream()?;
+        while stream.advance() {
+            let term = String::from_utf8_lossy(stream.key()).to_string();
+            *doc_freqs.entry(term).or_default() += stream.value().doc_freq as u64;
+        }
+    }
+
+    Ok(doc_freqs
+        .into_iter()
+        .filter(|(term, _)| term != token)
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+        .map(|(term, _)| term))
+}
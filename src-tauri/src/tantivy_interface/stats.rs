@@ -0,0 +1,51 @@
+use super::facets::FacetCount;
+use super::ranking::now_in_millis;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::fs;
+use std::path::Path;
+use tantivy::{Index, IndexWriter};
+
+/// Health of the index, for the diagnostics panel.
+/// Deleted documents are kept in their segment until it is merged.
+/// `size_in_bytes` is `None` for an index in memory, and `last_commit_at`
+/// is `None` until the first commit.
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize)]
+pub struct IndexStats {
+    pub doc_types: Vec<FacetCount>,
+    pub deleted_docs: u32,
+    pub segments: u32,
+    pub size_in_bytes: Option<f64>,
+    pub last_commit_at: Option<f64>,
+}
+
+/// Commits the pending changes, recording the time of the commit
+/// as the payload of the index metadata.
+pub fn commit(writer: &mut IndexWriter) -> anyhow::Result<()> {
+    let mut prepared_commit = writer.prepare_commit()?;
+    prepared_commit.set_payload(&now_in_millis().to_string());
+    prepared_commit.commit()?;
+    Ok(())
+}
+
+pub fn last_commit_at(index: &Index) -> anyhow::Result<Option<f64>> {
+    Ok(index
+        .load_metas()?
+        .payload
+        .and_then(|payload| payload.parse::<f64>().ok()))
+}
+
+pub fn directory_size(path: &Path) -> anyhow::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            directory_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
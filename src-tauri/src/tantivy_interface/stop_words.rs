@@ -0,0 +1,159 @@
+use super::{normalize, settings};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use tauri::AppHandle;
+
+#[rustfmt::skip]
+mod lists;
+
+const SETTINGS_KEY: &str = "stop_word_languages";
+
+// Languages for which Snowball has a stop word list.
+#[cfg_attr(debug_assertions, derive(Type, Debug))]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum StopWordLanguage {
+    Danish,
+    Dutch,
+    English,
+    Finnish,
+    French,
+    German,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Russian,
+    Spanish,
+    Swedish,
+}
+
+impl StopWordLanguage {
+    fn words(&self) -> &'static [&'static str] {
+        match self {
+            StopWordLanguage::Danish => lists::DANISH,
+            StopWordLanguage::Dutch => lists::DUTCH,
+            StopWordLanguage::English => lists::ENGLISH,
+            StopWordLanguage::Finnish => lists::FINNISH,
+            StopWordLanguage::French => lists::FRENCH,
+            StopWordLanguage::German => lists::GERMAN,
+            StopWordLanguage::Hungarian => lists::HUNGARIAN,
+            StopWordLanguage::Italian => lists::ITALIAN,
+            StopWordLanguage::Norwegian => lists::NORWEGIAN,
+            StopWordLanguage::Portuguese => lists::PORTUGUESE,
+            StopWordLanguage::Russian => lists::RUSSIAN,
+            StopWordLanguage::Spanish => lists::SPANISH,
+            StopWordLanguage::Swedish => lists::SWEDISH,
+        }
+    }
+}
+
+// Stop words are only removed from queries (see `QueryBuilder::build`). The index
+// keeps every word so that quoted phrases like "the who" can still be matched.
+// The words are normalized and lowercased like the words of the query,
+// so that "meme" and "même" are both French stop words.
+static STOP_WORDS: RwLock<StopWords> = RwLock::new(StopWords {
+    languages: Vec::new(),
+    words: None,
+});
+
+// Each search takes a copy of the current stop words, so that changing
+// them doesn't affect the searches running meanwhile.
+#[derive(Clone)]
+pub struct StopWords {
+    languages: Vec<StopWordLanguage>,
+    words: Option<Arc<HashSet<String>>>,
+}
+
+impl StopWords {
+    pub fn new(languages: Vec<StopWordLanguage>) -> StopWords {
+        let mut unique_languages = vec![];
+        let mut words = HashSet::new();
+        for language in languages {
+            if unique_languages.contains(&language) {
+                continue;
+            }
+            unique_languages.push(language);
+            words.extend(language.words().iter().map(|word| key(word)));
+        }
+        StopWords {
+            languages: unique_languages,
+            words: Some(Arc::new(words)),
+        }
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.words
+            .as_ref()
+            .is_some_and(|words| words.contains(&key(word)))
+    }
+}
+
+fn key(word: &str) -> String {
+    normalize(word).to_lowercase()
+}
+
+pub fn load(app_handle: &AppHandle) -> anyhow::Result<()> {
+    let languages = settings::read::<Vec<StopWordLanguage>>(app_handle, SETTINGS_KEY)?;
+    set(languages.unwrap_or_default())
+}
+
+pub fn save(app_handle: &AppHandle, languages: Vec<StopWordLanguage>) -> anyhow::Result<()> {
+    settings::write(app_handle, SETTINGS_KEY, &languages)?;
+    set(languages)
+}
+
+pub fn get() -> anyhow::Result<Vec<StopWordLanguage>> {
+    let stop_words = STOP_WORDS.read().map_err(|e| anyhow!(e.to_string()))?;
+    Ok(stop_words.languages.clone())
+}
+
+pub fn set(languages: Vec<StopWordLanguage>) -> anyhow::Result<()> {
+    let new_stop_words = StopWords::new(languages);
+    let mut stop_words = STOP_WORDS.write().map_err(|e| anyhow!(e.to_string()))?;
+    *stop_words = new_stop_words;
+    Ok(())
+}
+
+pub fn current() -> anyhow::Result<StopWords> {
+    let stop_words = STOP_WORDS.read().map_err(|e| anyhow!(e.to_string()))?;
+    Ok(stop_words.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        // The global stop words are left to the other tests.
+        let stop_words = StopWords::new(vec![
+            StopWordLanguage::English,
+            StopWordLanguage::German,
+            StopWordLanguage::French,
+            StopWordLanguage::English,
+        ]);
+        assert_eq!(
+            stop_words.languages,
+            vec![
+                StopWordLanguage::English,
+                StopWordLanguage::German,
+                StopWordLanguage::French
+            ]
+        );
+
+        assert!(stop_words.contains("the"));
+        assert!(stop_words.contains("The"));
+        assert!(stop_words.contains("der"));
+        assert!(stop_words.contains("même"));
+        assert!(stop_words.contains("meme"));
+        assert!(stop_words.contains("für"));
+        assert!(stop_words.contains("fur"));
+        assert!(!stop_words.contains("airport"));
+        assert!(!stop_words.contains("羽田"));
+
+        assert!(!StopWords::new(vec![]).contains("the"));
+    }
+}
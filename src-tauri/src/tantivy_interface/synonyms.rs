@@ -0,0 +1,93 @@
+use super::{normalize, settings};
+use anyhow::anyhow;
+use std::sync::RwLock;
+use tauri::AppHandle;
+
+const SETTINGS_KEY: &str = "synonyms";
+
+// Each group is a set of words that are treated as equivalent at query time,
+// e.g. ["k8s", "kubernetes"]. Documents are indexed as they are, so editing
+// the dictionary takes effect on the next search without reindexing.
+static SYNONYMS: RwLock<Vec<Vec<String>>> = RwLock::new(Vec::new());
+
+pub fn load(app_handle: &AppHandle) -> anyhow::Result<()> {
+    let groups = settings::read::<Vec<Vec<String>>>(app_handle, SETTINGS_KEY)?;
+    set(groups.unwrap_or_default())
+}
+
+pub fn save(app_handle: &AppHandle, groups: Vec<Vec<String>>) -> anyhow::Result<()> {
+    settings::write(app_handle, SETTINGS_KEY, &groups)?;
+    set(groups)
+}
+
+pub fn get() -> anyhow::Result<Vec<Vec<String>>> {
+    let synonyms = SYNONYMS.read().map_err(|e| anyhow!(e.to_string()))?;
+    Ok(synonyms.clone())
+}
+
+pub fn set(groups: Vec<Vec<String>>) -> anyhow::Result<()> {
+    let groups = clean(groups);
+    let mut synonyms = SYNONYMS.write().map_err(|e| anyhow!(e.to_string()))?;
+    *synonyms = groups;
+    Ok(())
+}
+
+// Drops blank words, and groups left with a single word.
+fn clean(groups: Vec<Vec<String>>) -> Vec<Vec<String>> {
+    groups
+        .into_iter()
+        .map(|group| {
+            group
+                .into_iter()
+                .map(|word| word.trim().to_string())
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<String>>()
+        })
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+/// Returns every word of the synonym group the given word belongs to,
+/// including the word itself, or an empty list if it has no synonyms.
+pub fn find_group(groups: &[Vec<String>], word: &str) -> Vec<String> {
+    let key = normalize(word).to_lowercase();
+    let group = groups.iter().find(|group| {
+        group
+            .iter()
+            .any(|synonym| normalize(synonym).to_lowercase() == key)
+    });
+    group.cloned().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        // The global synonyms are left to the other tests.
+        let groups = clean(vec![
+            vec![String::from("k8s"), String::from("kubernetes")],
+            vec![String::from("JS"), String::from("JavaScript")],
+            vec![String::from("羽田"), String::from("HND")],
+            vec![String::from("CI"), String::from("continuous integration")],
+            vec![String::from("alone"), String::from(" ")],
+        ]);
+        assert_eq!(groups.len(), 4);
+
+        assert_eq!(
+            find_group(&groups, "K8S"),
+            vec![String::from("k8s"), String::from("kubernetes")]
+        );
+        assert_eq!(
+            find_group(&groups, "hnd"),
+            vec![String::from("羽田"), String::from("HND")]
+        );
+        assert_eq!(
+            find_group(&groups, "ci"),
+            vec![String::from("CI"), String::from("continuous integration")]
+        );
+        assert!(find_group(&groups, "alone").is_empty());
+        assert!(find_group(&groups, "deploy").is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+use super::{find_document, ranking};
+use anyhow::anyhow;
+use tantivy::collector::DocSetCollector;
+use tantivy::query::TermQuery;
+use tantivy::schema::{Facet, Field, IndexRecordOption, OwnedValue, Term, Value};
+use tantivy::{IndexWriter, Searcher, TantivyDocument};
+
+pub const THREAD_PATH: &str = "thread_path";
+
+// The ancestry of a document is indexed as a facet made of thread ids,
+// from the root thread down to the thread containing the document,
+// e.g. "/root-id/parent-id/thread-id". Tantivy indexes every prefix
+// of a facet, so a term of a thread's path matches its whole subtree.
+
+pub fn facet(path: &[String]) -> Facet {
+    Facet::from_path(path)
+}
+
+/// Looks up the path of the thread from its own document.
+pub fn find_path(
+    searcher: &Searcher,
+    id_field: Field,
+    path_field: Field,
+    thread_id: &str,
+) -> anyhow::Result<Option<Facet>> {
+    let Some(document) = find_document(searcher, id_field, thread_id)? else {
+        return Ok(None);
+    };
+    match document.get_first(path_field) {
+        Some(OwnedValue::Facet(facet)) => Ok(Some(facet.clone())),
+        _ => Ok(None),
+    }
+}
+
+pub struct Fields {
+    pub id: Field,
+    pub thread_path: Field,
+    pub outline: Field,
+    pub title: Field,
+    pub text: Field,
+    pub tags: Field,
+    pub words: Field,
+}
+
+/// Re-indexes every document of the subtree at `old_path` under `new_path`.
+/// `new_outline` is the outline path of the moved thread itself.
+/// The documents are rebuilt from their stored fields, and their words,
+/// which are not stored, from their title, text and tags.
+pub fn move_subtree(
+    searcher: &Searcher,
+    writer: &mut IndexWriter,
+    fields: &Fields,
+    old_path: &Facet,
+    new_path: &Facet,
+    new_outline: &[String],
+) -> anyhow::Result<()> {
+    let query = TermQuery::new(
+        Term::from_facet(fields.thread_path, old_path),
+        IndexRecordOption::Basic,
+    );
+    let depth = old_path.to_path().len();
+
+    for address in searcher.search(&query, &DocSetCollector)? {
+        let old_document = searcher.doc::<TantivyDocument>(address)?;
+        let id = old_document
+            .get_first(fields.id)
+            .and_then(|value| value.as_str())
+            .ok_or(anyhow!("id field of the moved document is not defined!"))?;
+
+        let mut new_document = TantivyDocument::new();
+        for field_value in old_document.field_values() {
+            let field = field_value.field();
+            match field_value.value() {
+                OwnedValue::Facet(facet) if field == fields.thread_path => {
+                    let mut path = new_path.to_path();
+                    path.extend(facet.to_path().into_iter().skip(depth));
+                    new_document.add_facet(field, Facet::from_path(path));
+                }
+                OwnedValue::Str(key) if field == fields.outline => {
+                    let mut outline = new_outline.iter().map(String::as_str).collect::<Vec<_>>();
+                    outline.extend(ranking::split_outline_key(key).into_iter().skip(depth));
+                    new_document.add_text(field, ranking::outline_key(&outline));
+                }
+                OwnedValue::Str(text)
+                    if [fields.title, fields.text, fields.tags].contains(&field) =>
+                {
+                    new_document.add_text(fields.words, text);
+                    new_document.add_text(field, text);
+                }
+                value => new_document.add_field_value(field, value.clone()),
+            }
+        }
+
+        writer.delete_term(Term::from_field_text(fields.id, id));
+        writer.add_document(new_document)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+/// Returns the Unix time in milliseconds encoded in a UUIDv7,
+/// or `None` if the id is not a UUIDv7.
+pub fn timestamp(id: &str) -> Option<f64> {
+    let hex = id.replace('-', "");
+    if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    // The version is the 13th digit and the variant is 0b10xx.
+    let version = u8::from_str_radix(&hex[12..13], 16).ok()?;
+    let variant = u8::from_str_radix(&hex[16..17], 16).ok()?;
+    if version != 7 || variant & 0b1100 != 0b1000 {
+        return None;
+    }
+
+    // The first 48 bits are the Unix time in milliseconds.
+    let millis = u64::from_str_radix(&hex[0..12], 16).ok()?;
+    Some(millis as f64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        // example of RFC 9562
+        assert_eq!(
+            timestamp("017f22e2-79b0-7cc3-98c4-dc0c0c07398f"),
+            Some(1645557742000.0)
+        );
+        assert_eq!(
+            timestamp("017F22E279B07CC398C4DC0C0C07398F"),
+            Some(1645557742000.0)
+        );
+
+        // UUIDv4
+        assert_eq!(timestamp("f47ac10b-58cc-4372-a567-0e02b2c3d479"), None);
+        // wrong variant
+        assert_eq!(timestamp("017f22e2-79b0-7cc3-c8c4-dc0c0c07398f"), None);
+        assert_eq!(timestamp("1"), None);
+        assert_eq!(timestamp("017f22e2-79b0-7cc3-98c4-dc0c0c07398g"), None);
+    }
+}
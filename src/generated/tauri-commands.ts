@@ -16,6 +16,10 @@ async init() : Promise<Result<null, string>> {
     else return { status: "error", error: e  as any };
 }
 },
+/**
+ * Writes made while another program holds the writer lock of the index
+ * are queued, and applied in order once the lock is released.
+ */
 async index(input: IndexTarget[]) : Promise<Result<null, string>> {
     try {
     return { status: "ok", data: await TAURI_INVOKE("index", { input }) };
@@ -24,9 +28,171 @@ async index(input: IndexTarget[]) : Promise<Result<null, string>> {
     else return { status: "error", error: e  as any };
 }
 },
-async search(query: string, levenshteinDistance: number, limit: number) : Promise<Result<SearchResult[], string>> {
+/**
+ * Moves the thread under the thread at `parent_path`, an empty path
+ * meaning the root. Its cards and descendant threads are moved along.
+ * `outline_path` is the new outline path of the moved thread.
+ * Like `index`, the move is queued while the index is locked.
+ */
+async moveThread(threadId: string, parentPath: string[], outlinePath: string[]) : Promise<Result<null, string>> {
     try {
-    return { status: "ok", data: await TAURI_INVOKE("search", { query, levenshteinDistance, limit }) };
+    return { status: "ok", data: await TAURI_INVOKE("move_thread", { threadId, parentPath, outlinePath }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Returns the document counts and the storage usage of the index.
+ */
+async indexStats() : Promise<Result<IndexStats, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("index_stats") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Merges the segments of the index, purging the documents deleted by
+ * updates, and deletes the files the index no longer uses.
+ */
+async compact() : Promise<Result<CompactionReport, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("compact") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async search(query: QueryNode, sessionId: string | null, options: SearchOptions) : Promise<Result<SearchResponse, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("search", { query, sessionId, options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Cancels the in-flight search of the session, if any.
+ * The cancelled search returns the `SEARCH_CANCELLED` error.
+ */
+async cancelSearch(sessionId: string) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("cancel_search", { sessionId }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Explains how the query matches the document, to diagnose rankings from
+ * the devtools. `explanation` is tantivy's `Explanation` of the relevance
+ * score as JSON, and is `None` when the document doesn't match.
+ * Sort orders and the recency boost are applied on top of this score.
+ */
+async explain(query: QueryNode, id: string, options: SearchOptions) : Promise<Result<SearchExplanation, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("explain", { query, id, options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Runs the text through the normalization and the analyzer used for
+ * indexing or searching, to find out why a note is not found.
+ */
+async analyze(text: string, mode: AnalyzerMode) : Promise<Result<Analysis, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("analyze", { text, mode }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Returns the cards most similar to the given card, excluding the cards of
+ * its own thread when `exclude_thread` is set.
+ */
+async related(id: string, limit: number, excludeThread: boolean) : Promise<Result<SearchResult[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("related", { id, limit, excludeThread }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Returns the text with its misspelled words replaced by the most frequent
+ * similar words of the index, when the text has few hits with the options
+ * and the replaced text has more. Suggested words are taken from the
+ * dictionary of the index, so they are lowercased, e.g. "Conection" gives
+ * "connection".
+ */
+async suggest(text: string, options: SearchOptions) : Promise<Result<string | null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("suggest", { text, options }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+/**
+ * Completes the text being typed in the search box with the titles of the
+ * threads matching it, then with the most frequent words of the index.
+ */
+async autocomplete(prefix: string, limit: number) : Promise<Result<Completion[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("autocomplete", { prefix, limit }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getSynonyms() : Promise<Result<string[][], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_synonyms") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async setSynonyms(groups: string[][]) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("set_synonyms", { groups }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getStopWordLanguages() : Promise<Result<StopWordLanguage[], string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_stop_word_languages") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async setStopWordLanguages(languages: StopWordLanguage[]) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("set_stop_word_languages", { languages }) };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async getFieldBoosts() : Promise<Result<FieldBoosts, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("get_field_boosts") };
+} catch (e) {
+    if(e instanceof Error) throw e;
+    else return { status: "error", error: e  as any };
+}
+},
+async setFieldBoosts(boosts: FieldBoosts) : Promise<Result<null, string>> {
+    try {
+    return { status: "ok", data: await TAURI_INVOKE("set_field_boosts", { boosts }) };
 } catch (e) {
     if(e instanceof Error) throw e;
     else return { status: "error", error: e  as any };
@@ -40,12 +206,93 @@ async search(query: string, levenshteinDistance: number, limit: number) : Promis
 
 /** user-defined constants **/
 
+export const RECREATED_EVENT = "index-recreated" as const;
+export const COMPACTED_EVENT = "index-compacted" as const;
+export const INDEX_LOCKED = "The index is locked by another process" as const;
+export const SEARCH_CANCELLED = "Search was cancelled" as const;
 
 
 /** user-defined types **/
 
-export type IndexTarget = { id: string; doc_type: string; text: string }
+export type Analysis = { normalized: string; tokens: AnalyzedToken[] }
+export type AnalyzedToken = { text: string; offset_from: number; offset_to: number; position: number }
+/**
+ * `Index` is the analyzer of the indexed documents, and `Query` the one of
+ * the search words, which omits the single last character of a CJK run.
+ */
+export type AnalyzerMode = "Index" | "Query"
+/**
+ * `reclaimed_bytes` is `None` for an index in memory.
+ */
+export type CompactionReport = { merged_segments: number; purged_docs: number; deleted_files: number; reclaimed_bytes: number | null }
+export type Completion = { text: string; thread_id: string | null }
+export type DateField = "CreatedAt" | "UpdatedAt"
+export type FacetCount = { value: string; count: number }
+/**
+ * Number of matching documents per value, most frequent first.
+ * `threads` counts documents per top-level thread.
+ */
+export type Facets = { doc_types: FacetCount[]; threads: FacetCount[]; tags: FacetCount[] }
+/**
+ * Weight of a match in each field when a query is not scoped to a field.
+ */
+export type FieldBoosts = { title: number; text: number; tags: number }
+/**
+ * Maximum edit distance allowed between query words and indexed words.
+ * `Auto` picks the distance from the length of each word.
+ * Fuzziness is never applied to CJK tokens: a typo in a bigram is
+ * a different word rather than a misspelling.
+ */
+export type Fuzziness = { kind: "Auto" } | { kind: "Fixed"; distance: number }
+/**
+ * Sent when the index was recreated empty, so that the frontend indexes
+ * every card and thread of the database again.
+ * `quarantine_path` holds the files of a corrupted index, kept for
+ * inspection, and is `None` when the index only had an older schema.
+ */
+export type IndexRecreated = { reason: string; quarantine_path: string | null }
+/**
+ * Health of the index, for the diagnostics panel.
+ * Deleted documents are kept in their segment until it is merged.
+ * `size_in_bytes` is `None` for an index in memory, and `last_commit_at`
+ * is `None` until the first commit.
+ */
+export type IndexStats = { doc_types: FacetCount[]; deleted_docs: number; segments: number; size_in_bytes: number | null; last_commit_at: number | null }
+export type IndexTarget = { id: string; doc_type: string; title: string | null; text: string; tags: string[] | null; created_at: number | null; updated_at: number | null; thread_path: string[] | null; outline_path: string[] | null }
+export type QueryField = "Title" | "Text" | "Tags"
+/**
+ * Query tree accepted by `search`.
+ * `Text` holds raw input of the search box and is parsed leniently,
+ * so it can be mixed with nodes built programmatically by the frontend.
+ */
+export type QueryNode = { kind: "Text"; text: string } | { kind: "Term"; text: string; field: QueryField | null } | { kind: "Phrase"; text: string; field: QueryField | null; slop: number } | { kind: "Near"; texts: string[]; field: QueryField | null; distance: number } | { kind: "Prefix"; text: string; field: QueryField | null } | { kind: "Regex"; pattern: string; field: QueryField | null } | { kind: "Wildcard"; pattern: string; field: QueryField | null } | { kind: "DocType"; doc_type: string } | { kind: "Thread"; thread_id: string } | { kind: "DateRange"; field: DateField; from: number | null; to: number | null } | { kind: "Bool"; must: QueryNode[]; should: QueryNode[]; must_not: QueryNode[]; filter: QueryNode[] }
+/**
+ * Multiplies the relevance score of recently updated documents by up to
+ * `1 + weight`. The extra weight halves every `half_life_days`.
+ */
+export type RecencyBoost = { weight: number; half_life_days: number }
+export type SearchExplanation = { tree: QueryNode; tokens: string[]; score: number | null; explanation: string | null }
+/**
+ * `Standard` prefix-expands every word of the query.
+ * `Incremental` is meant for search-as-you-type: only the word being typed
+ * is prefix-expanded, and the other words must match as complete words.
+ */
+export type SearchMode = "Standard" | "Incremental"
+export type SearchOptions = { mode: SearchMode; fuzziness: Fuzziness; limit: number; sort_by: SortBy; recency_boost: RecencyBoost | null; facets: boolean; hits_per_thread: number | null }
+/**
+ * Threads are ranked by the sum of the scores of their hits in grouped
+ * searches, which fill `groups` instead of `results`.
+ */
+export type SearchResponse = { results: SearchResult[]; groups: ThreadGroup[] | null; facets: Facets | null }
 export type SearchResult = { id: string; doc_type: string }
+/**
+ * Order of the search results. Date orders list the newest documents first.
+ * `Outline` lists documents in the order of the thread tree, as `getNode`
+ * orders children by `fractional_index`.
+ */
+export type SortBy = "Relevance" | "CreatedAt" | "UpdatedAt" | "Outline"
+export type StopWordLanguage = "Danish" | "Dutch" | "English" | "Finnish" | "French" | "German" | "Hungarian" | "Italian" | "Norwegian" | "Portuguese" | "Russian" | "Spanish" | "Swedish"
+export type ThreadGroup = { thread_id: string | null; hit_count: number; hits: SearchResult[] }
 
 /** tauri-specta globals **/
 
@@ -0,0 +1,58 @@
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import {
+  commands,
+  COMPACTED_EVENT,
+  INDEX_LOCKED,
+  RECREATED_EVENT,
+  SEARCH_CANCELLED,
+  type CompactionReport,
+  type IndexRecreated,
+  type IndexTarget,
+} from "../../generated/tauri-commands";
+
+export const SearchIndex = {
+  // The index is recreated empty when its schema changed or its files are
+  // corrupted, so every document is indexed again. The listeners must be
+  // registered before `init`, which emits the event.
+  async init(getDocuments: () => Promise<IndexTarget[]>): Promise<UnlistenFn> {
+    const unlistenRecreated = await listen<IndexRecreated>(
+      RECREATED_EVENT,
+      async (event) => {
+        console.warn("search index recreated:", event.payload);
+        const res = await commands.index(await getDocuments());
+        if (res.status === "error") {
+          console.error(res.error);
+        }
+      },
+    );
+    // The index is compacted in the background once the app is idle.
+    const unlistenCompacted = await listen<CompactionReport>(
+      COMPACTED_EVENT,
+      (event) => console.info("search index compacted:", event.payload),
+    );
+    const unlisten = () => {
+      unlistenRecreated();
+      unlistenCompacted();
+    };
+
+    const res = await commands.init();
+    if (res.status === "error") {
+      unlisten();
+      throw new Error(res.error);
+    }
+    return unlisten;
+  },
+
+  // Another program holds the writer lock of an index which can't be
+  // opened, so `init` fails. Writes made while it holds the lock of an
+  // index which could be opened are applied once the lock is released.
+  isLocked(error: string): boolean {
+    return error === INDEX_LOCKED;
+  },
+
+  // A search superseded by a newer one of the same session, or cancelled
+  // with `cancelSearch`, which is not worth reporting.
+  isCancelled(error: string): boolean {
+    return error === SEARCH_CANCELLED;
+  },
+} as const;